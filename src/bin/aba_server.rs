@@ -3,12 +3,13 @@ use std::fmt::{Display, Formatter};
 use std::io;
 use std::sync::Mutex;
 
+use actix_web::http::StatusCode;
 use actix_web::{
     get, middleware, post, web, App, Error as AWError, HttpResponse, HttpServer, Responder,
     ResponseError,
 };
 
-use aba::journal::{test_entries, Journal, JournalEntry, OrganizationId};
+use aba::journal::{test_entries, Journal, JournalEntry, OrganizationId, UnitAmount};
 use aba::ledger::OrganizationLedgers;
 use aba::rusty_ulid;
 
@@ -46,7 +47,15 @@ impl From<aba::ledger::Error> for Error {
     }
 }
 
-impl ResponseError for Error {}
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Ledger(aba::ledger::Error::MissingOrganization(_)) => StatusCode::NOT_FOUND,
+            Self::Ledger(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 #[cfg(feature = "web-files")]
 include!(concat!(env!("OUT_DIR"), "/generated.rs"));
@@ -84,7 +93,8 @@ async fn main() -> io::Result<()> {
                 .service(view_ledger_accounts)
                 .service(view_ledger_currencies)
                 .service(view_ledger_contacts)
-                .service(view_ledger_transactions),
+                .service(view_ledger_transactions)
+                .service(convert_unit_amount),
         );
         #[cfg(feature = "web-files")]
         let app = app.service(ResourceFiles::new("/", generate()));
@@ -209,3 +219,20 @@ async fn view_ledger_transactions(
         .transactions();
     Ok(web::Json(transactions_view))
 }
+
+/// Convert an amount in any declared unit of a currency to currency units
+#[post("/ledger/{organization}/amount")]
+async fn convert_unit_amount(
+    organization_ledgers: web::Data<Mutex<OrganizationLedgers>>,
+    organization_id: web::Path<OrganizationId>,
+    unit_amount: web::Json<UnitAmount>,
+) -> Result<impl Responder, AWError> {
+    let currency_amount = organization_ledgers
+        .lock()
+        .unwrap()
+        .get_ledger(&organization_id.into_inner())
+        .map_err(|e| Error::Ledger(e))?
+        .unit_amount(&unit_amount.0)
+        .map_err(|e| Error::Ledger(e))?;
+    Ok(web::Json(currency_amount))
+}
@@ -1,3 +1,4 @@
+use crate::journal::money::Money;
 use crate::journal::Action::{
     AddAccount, AddContact, AddCurrency, AddOrganization, AddTransaction,
 };
@@ -6,11 +7,14 @@ use rust_decimal::Decimal;
 use rusty_ulid::Ulid;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 use time::macros::datetime;
-use time::{Date, OffsetDateTime};
+use time::{Date, Duration, Month, OffsetDateTime};
 
+pub mod money;
 #[cfg(feature = "server")]
 pub mod sqlite;
 
@@ -146,13 +150,50 @@ pub enum Action {
     AddContact {
         contact: Contact,
     },
+    UpdateContact {
+        contact: Contact,
+    },
     AddAccount {
         account: Account,
     },
+    UpdateAccount {
+        account: Account,
+    },
+    ArchiveAccount {
+        account_id: AccountId,
+        datetime: OffsetDateTime,
+    },
     AddTransaction {
         transaction: Transaction,
         ledger_entries: Vec<LedgerEntry>,
     },
+    ReverseTransaction {
+        transaction: Transaction,
+    },
+    VoidTransaction {
+        void: TransactionVoid,
+    },
+    ClosePeriod {
+        period_close: PeriodClose,
+    },
+    AddExchangeRate {
+        exchange_rate: ExchangeRate,
+    },
+    Reconcile {
+        reconciliation: Reconciliation,
+    },
+    AddPayment {
+        transaction_id: TransactionId,
+        payment: Payment,
+        transaction: Transaction,
+        ledger_entries: Vec<LedgerEntry>,
+    },
+    SchedulePayment {
+        payment_schedule: PaymentSchedule,
+    },
+    AddTaxCode {
+        tax_code: TaxCode,
+    },
 }
 
 /// Organization id
@@ -171,12 +212,55 @@ pub type ContactId = Ulid;
 pub struct Organization {
     pub id: OrganizationId,
     pub contact_id: ContactId,
+    #[serde(default = "Organization::default_fiscal_year_start")]
+    pub fiscal_year_start: Month,
+    #[serde(default = "Organization::default_functional_currency_id")]
+    pub functional_currency_id: CurrencyId,
+    /// Unit code amounts of a currency are displayed in, ie. "sat" for BTC
+    #[serde(default)]
+    pub display_units: BTreeMap<CurrencyId, String>,
 }
 impl Organization {
     pub fn new(contact_id: &ContactId) -> Self {
+        Organization::new_fiscal_year(contact_id, Organization::default_fiscal_year_start())
+    }
+
+    pub fn new_fiscal_year(contact_id: &ContactId, fiscal_year_start: Month) -> Self {
         let id = Ulid::generate();
         let contact_id = contact_id.clone();
-        Organization { id, contact_id }
+        let functional_currency_id = Organization::default_functional_currency_id();
+        Organization {
+            id,
+            contact_id,
+            fiscal_year_start,
+            functional_currency_id,
+            display_units: BTreeMap::new(),
+        }
+    }
+
+    pub fn display_unit(&self, currency_id: &CurrencyId) -> Option<&String> {
+        self.display_units.get(currency_id)
+    }
+
+    fn default_fiscal_year_start() -> Month {
+        Month::January
+    }
+
+    fn default_functional_currency_id() -> CurrencyId {
+        USD as CurrencyId
+    }
+
+    /// First instant of the fiscal year that starts in the given calendar year
+    pub fn fiscal_year_start_date_time(&self, year: i32) -> OffsetDateTime {
+        Date::from_calendar_date(year, self.fiscal_year_start, 1)
+            .expect("first day of month")
+            .midnight()
+            .assume_utc()
+    }
+
+    /// Last instant of the fiscal year that starts in the given calendar year
+    pub fn fiscal_year_end_date_time(&self, year: i32) -> OffsetDateTime {
+        self.fiscal_year_start_date_time(year + 1) - Duration::nanoseconds(1)
     }
 }
 
@@ -299,6 +383,20 @@ impl fmt::Display for AccountCategory {
     }
 }
 
+impl AccountCategory {
+    /// Entry type that increases an account balance, ie. debit for assets and expenses
+    pub fn normal_entry_type(&self) -> EntryType {
+        match self {
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset)
+            | AccountCategory::IncomeStatement(IncomeStatementCategory::OperatingExpense)
+            | AccountCategory::IncomeStatement(IncomeStatementCategory::NonOperatingExpense) => {
+                EntryType::Debit
+            }
+            _ => EntryType::Credit,
+        }
+    }
+}
+
 impl Account {
     pub fn new(
         parent_id: Option<&AccountId>,
@@ -331,6 +429,16 @@ pub enum CurrencyCode {
 /// Currency scale
 pub type CurrencyScale = u32;
 
+// largest scale a decimal amount can have
+pub const MAX_CURRENCY_SCALE: CurrencyScale = 28;
+
+/// BTC currency scale, on-chain amounts are in satoshis
+pub const BTC_SCALE: CurrencyScale = 8;
+
+pub fn btc_currency_id() -> CurrencyId {
+    CurrencyCode::BTC as CurrencyId
+}
+
 /// Units for a fiat currency value, ie. USD, EUR
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Currency {
@@ -338,6 +446,94 @@ pub struct Currency {
     pub code: String,
     pub scale: CurrencyScale,
     pub name: String,
+    #[serde(default)]
+    pub units: Vec<CurrencyUnit>,
+}
+
+impl Currency {
+    // currency unit or declared sub-unit with a matching code
+    pub fn get_unit(&self, code: &str) -> Option<CurrencyUnit> {
+        if self.code.eq_ignore_ascii_case(code) {
+            return Some(CurrencyUnit::new(self.code.clone(), self.name.clone(), 0));
+        }
+        self.units
+            .iter()
+            .find(|unit| unit.code.eq_ignore_ascii_case(code))
+            .cloned()
+    }
+
+    // amount in currency units converted to a unit
+    pub fn to_unit(&self, amount: Decimal, code: &str) -> Option<Decimal> {
+        let unit = self.get_unit(code)?;
+        amount.checked_div(Decimal::try_new(1, unit.scale).ok()?)
+    }
+
+    // amount in a unit converted to currency units
+    pub fn from_unit(&self, amount: Decimal, code: &str) -> Option<Decimal> {
+        let unit = self.get_unit(code)?;
+        amount.checked_mul(Decimal::try_new(1, unit.scale).ok()?)
+    }
+
+    // scale of the smallest declared unit
+    pub fn finest_scale(&self) -> CurrencyScale {
+        self.units
+            .iter()
+            .map(|unit| unit.scale)
+            .fold(self.scale, CurrencyScale::max)
+    }
+}
+
+/// Named fraction of a currency unit, scale is its decimal places in currency units, ie. sat
+/// has scale 8 and msat scale 11 for BTC
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct CurrencyUnit {
+    pub code: String,
+    pub name: String,
+    pub scale: CurrencyScale,
+}
+
+impl CurrencyUnit {
+    pub fn new(code: String, name: String, scale: CurrencyScale) -> Self {
+        CurrencyUnit { code, name, scale }
+    }
+}
+
+/// Amount of a currency in one of its units, currency units if no unit is given
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct UnitAmount {
+    pub currency_id: CurrencyId,
+    pub amount: Decimal,
+    pub unit: Option<String>,
+}
+
+/// Quote currency amount of one unit of the base currency from a source at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ExchangeRate {
+    pub base_currency_id: CurrencyId,
+    pub quote_currency_id: CurrencyId,
+    pub rate: Decimal,
+    pub datetime: OffsetDateTime,
+    pub source: String,
+}
+
+impl ExchangeRate {
+    pub fn new(
+        base_currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        rate: Decimal,
+        datetime: OffsetDateTime,
+        source: String,
+    ) -> Self {
+        let base_currency_id = *base_currency_id;
+        let quote_currency_id = *quote_currency_id;
+        ExchangeRate {
+            base_currency_id,
+            quote_currency_id,
+            rate,
+            datetime,
+            source,
+        }
+    }
 }
 
 /// Ledger entry types
@@ -374,12 +570,203 @@ impl Transaction {
             transaction_type,
         }
     }
+
+    pub fn new_reversal(
+        datetime: OffsetDateTime,
+        description: String,
+        transaction_id: &TransactionId,
+    ) -> Self {
+        let transaction_type = TransactionType::Reversal {
+            transaction_id: *transaction_id,
+        };
+        Transaction::new(datetime, description, transaction_type)
+    }
+}
+
+/// Voided transaction remains in the ledger but its entries are excluded from totals
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TransactionVoid {
+    pub transaction_id: TransactionId,
+    pub datetime: OffsetDateTime,
+    pub description: String,
+}
+
+impl TransactionVoid {
+    pub fn new(
+        transaction_id: &TransactionId,
+        datetime: OffsetDateTime,
+        description: String,
+    ) -> Self {
+        let transaction_id = *transaction_id;
+        TransactionVoid {
+            transaction_id,
+            datetime,
+            description,
+        }
+    }
+}
+
+/// Closing of a period's income statement accounts to a retained earnings account, after which
+/// no transactions can be added in the period
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct PeriodClose {
+    pub transaction_id: TransactionId,
+    pub start_date_time: OffsetDateTime,
+    pub end_date_time: OffsetDateTime,
+    pub retained_earnings_account_id: AccountId,
+}
+
+impl PeriodClose {
+    pub fn new(
+        start_date_time: OffsetDateTime,
+        end_date_time: OffsetDateTime,
+        retained_earnings_account_id: &AccountId,
+    ) -> Self {
+        let transaction_id = Ulid::generate();
+        let retained_earnings_account_id = *retained_earnings_account_id;
+        PeriodClose {
+            transaction_id,
+            start_date_time,
+            end_date_time,
+            retained_earnings_account_id,
+        }
+    }
+
+    pub fn new_fiscal_year(
+        organization: &Organization,
+        year: i32,
+        retained_earnings_account_id: &AccountId,
+    ) -> Self {
+        PeriodClose::new(
+            organization.fiscal_year_start_date_time(year),
+            organization.fiscal_year_end_date_time(year),
+            retained_earnings_account_id,
+        )
+    }
+
+    pub fn contains(&self, datetime: &OffsetDateTime) -> bool {
+        &self.start_date_time <= datetime && datetime <= &self.end_date_time
+    }
+}
+
+/// Transactions of an account matched against an external record, such as on-chain wallet
+/// transactions, with the external balance and any difference of the account balance from it
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Reconciliation {
+    pub account_id: AccountId,
+    pub datetime: OffsetDateTime,
+    pub transaction_ids: Vec<TransactionId>,
+    #[serde(default)]
+    pub balance: Option<CurrencyAmount>,
+    #[serde(default)]
+    pub difference: Decimal,
+}
+
+impl Reconciliation {
+    pub fn new(
+        account_id: &AccountId,
+        datetime: OffsetDateTime,
+        transaction_ids: Vec<TransactionId>,
+        balance: Option<CurrencyAmount>,
+        difference: Decimal,
+    ) -> Self {
+        let account_id = *account_id;
+        Reconciliation {
+            account_id,
+            datetime,
+            transaction_ids,
+            balance,
+            difference,
+        }
+    }
+}
+
+/// Planned full or partial payment of a bill at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct PaymentSchedule {
+    pub transaction_id: TransactionId,
+    pub datetime: OffsetDateTime,
+    pub currency_amount: CurrencyAmount,
+}
+
+impl PaymentSchedule {
+    pub fn new(
+        transaction_id: &TransactionId,
+        datetime: OffsetDateTime,
+        currency_amount: CurrencyAmount,
+    ) -> Self {
+        let transaction_id = *transaction_id;
+        PaymentSchedule {
+            transaction_id,
+            datetime,
+            currency_amount,
+        }
+    }
+}
+
+/// Sales tax or VAT rate, ie. 0.08 for 8%, posted to a tax account such as a sales tax payable
+/// liability
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TaxCode {
+    pub code: String,
+    pub name: String,
+    pub rate: Decimal,
+    pub account_id: AccountId,
+}
+
+impl TaxCode {
+    pub fn new(code: String, name: String, rate: Decimal, account_id: &AccountId) -> Self {
+        let account_id = *account_id;
+        TaxCode {
+            code,
+            name,
+            rate,
+            account_id,
+        }
+    }
+}
+
+/// Invoice or bill line posted to a revenue or expense account, its amount is the quantity times
+/// the unit price
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct LineItem {
+    pub description: String,
+    pub quantity: Decimal,
+    pub unit_price: CurrencyAmount,
+    pub account_id: AccountId,
+    pub tax_code: Option<String>,
+}
+
+impl LineItem {
+    pub fn new(
+        description: String,
+        quantity: Decimal,
+        unit_price: CurrencyAmount,
+        account_id: &AccountId,
+        tax_code: Option<String>,
+    ) -> Self {
+        let account_id = *account_id;
+        LineItem {
+            description,
+            quantity,
+            unit_price,
+            account_id,
+            tax_code,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum PaymentMethod {
     Bitcoin {
         address: String,
+        #[serde(default)]
+        account_id: Option<AccountId>,
+        #[serde(default)]
+        derivation_index: Option<u32>,
+    },
+    Lightning {
+        invoice: String,
     },
     Ach {
         contact_id: ContactId,
@@ -394,6 +781,8 @@ pub enum PaymentMethod {
     Cash,
 }
 
+/// Discounts and late fee interest are rates, ie. 0.02 for 2%, late fee interest is an annual
+/// rate accrued daily after the due date
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum PaymentTerms {
     ImmediatePayment,
@@ -410,13 +799,58 @@ pub enum PaymentTerms {
     },
 }
 
+impl PaymentTerms {
+    // date time payment is due for an invoice issued at date time
+    pub fn due_date_time(&self, datetime: OffsetDateTime) -> OffsetDateTime {
+        match self {
+            PaymentTerms::ImmediatePayment | PaymentTerms::PaymentInAdvance => datetime,
+            PaymentTerms::NetDays { days, .. } | PaymentTerms::NetDaysDiscount { days, .. } => {
+                datetime + Duration::days(*days as i64)
+            }
+        }
+    }
+
+    // last date time an early payment discount applies, if the terms offer one
+    pub fn discount_date_time(&self, datetime: OffsetDateTime) -> Option<OffsetDateTime> {
+        match self {
+            PaymentTerms::NetDaysDiscount { discount_days, .. } => {
+                Some(datetime + Duration::days(*discount_days as i64))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn discount(&self) -> Decimal {
+        match self {
+            PaymentTerms::NetDaysDiscount { discount, .. } => *discount,
+            _ => Decimal::ZERO,
+        }
+    }
+
+    pub fn late_fee_interest(&self) -> Decimal {
+        match self {
+            PaymentTerms::NetDays {
+                late_fee_interest, ..
+            }
+            | PaymentTerms::NetDaysDiscount {
+                late_fee_interest, ..
+            } => *late_fee_interest,
+            _ => Decimal::ZERO,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum Payment {
     Bitcoin {
         details: String,
     },
     Lightning {
-        details: String,
+        invoice: String,
+        payment_hash: String,
+        preimage: Option<String>,
+        amount_msat: u64,
+        settled: Option<OffsetDateTime>,
     },
     Ach {
         transaction_id: String,
@@ -441,14 +875,63 @@ pub enum Payment {
     },
 }
 
+/// Lot selected to dispose of for a specific identification disposal, or recorded with the
+/// realized gain of a disposal
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct LotSelection {
+    pub lot_id: TransactionId,
+    pub amount: Decimal,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub enum TransactionType {
+    // sales invoice, debits a contact receivable account, entries must match any line items
     Invoice {
         payment_method: PaymentMethod,
         payment_terms: PaymentTerms,
         payments: Vec<Payment>,
+        #[serde(default)]
+        line_items: Vec<LineItem>,
+    },
+    // vendor bill, credits a contact payable account, entries must match any line items
+    Bill {
+        payment_method: PaymentMethod,
+        payment_terms: PaymentTerms,
+        payments: Vec<Payment>,
+        #[serde(default)]
+        line_items: Vec<LineItem>,
     },
     LedgerAdjustment,
+    Reversal {
+        transaction_id: TransactionId,
+    },
+    PeriodClose,
+    OnChain {
+        txid: String,
+    },
+    // gain of a disposal, lots selected by the disposing transaction are kept so its cost basis
+    // does not change with the method
+    RealizedGain {
+        transaction_id: TransactionId,
+        #[serde(default)]
+        lot_selections: Vec<LotSelection>,
+    },
+    // revaluation of the foreign currency balances of one currency
+    Revaluation {
+        currency_id: CurrencyId,
+    },
+    InvoicePayment {
+        transaction_id: TransactionId,
+    },
+    WriteOff {
+        transaction_id: TransactionId,
+    },
+    LateFee {
+        transaction_id: TransactionId,
+    },
+    Discount {
+        transaction_id: TransactionId,
+    },
 }
 
 /// Account and currency amount of a debit or credit ledger entry
@@ -479,6 +962,37 @@ impl LedgerEntry {
             description,
         }
     }
+
+    // debit a positive amount or credit a negative amount of money already checked against its
+    // currency's scale
+    pub fn from_money(
+        transaction_id: &TransactionId,
+        account_id: &AccountId,
+        money: &Money,
+        description: Option<String>,
+    ) -> Self {
+        let entry_type = if money.amount.is_sign_negative() {
+            EntryType::Credit
+        } else {
+            EntryType::Debit
+        };
+        LedgerEntry::new(
+            transaction_id,
+            entry_type,
+            account_id,
+            CurrencyAmount::new(&money.currency.id, money.amount.abs()),
+            description,
+        )
+    }
+
+    // amount of the entry's currency checked against its payment rail's scale
+    pub fn money(
+        &self,
+        currency: &Arc<Currency>,
+        scale: CurrencyScale,
+    ) -> Result<Money, money::Error> {
+        Money::from_currency_amount(currency, &self.currency_amount, scale)
+    }
 }
 
 /// Currency and amount of a debit or credit
@@ -593,6 +1107,7 @@ pub fn test_entries() -> TestEntries {
         code: "USD".to_string(),
         scale: 2,
         name: "US Dollars".to_string(),
+        units: vec![],
     };
 
     let btc = Currency {
@@ -600,6 +1115,10 @@ pub fn test_entries() -> TestEntries {
         code: "BTC".to_string(),
         scale: 8,
         name: "Bitcoin".to_string(),
+        units: vec![
+            CurrencyUnit::new("sat".to_string(), "Satoshi".to_string(), 8),
+            CurrencyUnit::new("msat".to_string(), "Millisatoshi".to_string(), 11),
+        ],
     };
 
     let currencies = vec![usd.clone(), btc.clone()];
@@ -695,6 +1214,7 @@ pub fn test_entries() -> TestEntries {
             payment_method: PaymentMethod::Cash,
             payment_terms: PaymentTerms::ImmediatePayment,
             payments: vec![],
+            line_items: vec![],
         },
     );
 
@@ -732,6 +1252,7 @@ pub fn test_entries() -> TestEntries {
             },
             payment_terms: PaymentTerms::ImmediatePayment,
             payments: vec![],
+            line_items: vec![],
         },
     );
 
@@ -0,0 +1,359 @@
+use crate::journal::{Currency, CurrencyAmount, CurrencyId, CurrencyScale, Organization};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    CurrencyMismatch(CurrencyId, CurrencyId),
+    ScaleExceeded(CurrencyId),
+    InvalidAmount(String),
+    UnknownCurrency(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CurrencyMismatch(a, b) => write!(f, "currency mismatch: {} {}", a, b),
+            Self::ScaleExceeded(c) => write!(f, "currency scale exceeded: {}", c),
+            Self::InvalidAmount(a) => write!(f, "invalid amount: {}", a),
+            Self::UnknownCurrency(c) => write!(f, "unknown currency: {}", c),
+        }
+    }
+}
+
+/// Rounding of amounts with more decimal places than their currency's scale
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RoundingMode {
+    // banker's rounding, midpoints round to the nearest even digit
+    HalfEven,
+    HalfUp,
+}
+
+impl RoundingMode {
+    fn strategy(&self) -> RoundingStrategy {
+        match self {
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+        }
+    }
+}
+
+/// Amount of a currency with no more decimal places than the currency's scale, or than the
+/// scale of a finer unit for payment rails that settle in it, ie. msat for lightning
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Money {
+    pub currency: Arc<Currency>,
+    pub amount: Decimal,
+}
+
+impl Money {
+    pub fn new(currency: &Arc<Currency>, amount: Decimal) -> Result<Self, Error> {
+        Money::with_scale(currency, amount, currency.scale)
+    }
+
+    // amount with no more decimal places than a rail's scale, which may be finer than the
+    // currency's scale up to its finest unit
+    pub fn with_scale(
+        currency: &Arc<Currency>,
+        amount: Decimal,
+        scale: CurrencyScale,
+    ) -> Result<Self, Error> {
+        if scale > currency.finest_scale() || amount.normalize().scale() > scale {
+            return Err(Error::ScaleExceeded(currency.id));
+        }
+        let mut amount = amount;
+        amount.rescale(scale.max(currency.scale));
+        Ok(Money {
+            currency: currency.clone(),
+            amount,
+        })
+    }
+
+    // amount in the currency's smallest unit, ie. cents or satoshis
+    pub fn from_minor(currency: &Arc<Currency>, minor: i64) -> Self {
+        Money {
+            currency: currency.clone(),
+            amount: Decimal::new(minor, currency.scale),
+        }
+    }
+
+    pub fn zero(currency: &Arc<Currency>) -> Self {
+        Money::from_minor(currency, 0)
+    }
+
+    pub fn rounded(currency: &Arc<Currency>, amount: Decimal, mode: RoundingMode) -> Self {
+        let mut amount = amount.round_dp_with_strategy(currency.scale, mode.strategy());
+        amount.rescale(currency.scale);
+        Money {
+            currency: currency.clone(),
+            amount,
+        }
+    }
+
+    pub fn from_currency_amount(
+        currency: &Arc<Currency>,
+        currency_amount: &CurrencyAmount,
+        scale: CurrencyScale,
+    ) -> Result<Self, Error> {
+        if currency_amount.currency_id != currency.id {
+            return Err(Error::CurrencyMismatch(
+                currency.id,
+                currency_amount.currency_id,
+            ));
+        }
+        Money::with_scale(currency, currency_amount.amount, scale)
+    }
+
+    pub fn currency_amount(&self) -> CurrencyAmount {
+        CurrencyAmount::new(&self.currency.id, self.amount)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn checked_add(&self, other: &Money) -> Result<Self, Error> {
+        self.same_currency(other)?;
+        Money::with_scale(
+            &self.currency,
+            self.amount + other.amount,
+            self.amount.scale().max(other.amount.scale()),
+        )
+    }
+
+    pub fn checked_sub(&self, other: &Money) -> Result<Self, Error> {
+        self.same_currency(other)?;
+        Money::with_scale(
+            &self.currency,
+            self.amount - other.amount,
+            self.amount.scale().max(other.amount.scale()),
+        )
+    }
+
+    pub fn negate(&self) -> Self {
+        Money {
+            currency: self.currency.clone(),
+            amount: -self.amount,
+        }
+    }
+
+    // amount multiplied by a factor, ie. a rate or quantity, rounded to the currency's scale
+    pub fn multiply(&self, factor: Decimal, mode: RoundingMode) -> Self {
+        Money::rounded(&self.currency, self.amount * factor, mode)
+    }
+
+    // parse amounts with a currency or unit code before or after, ie. "USD 1,234.56",
+    // "0.00012345 BTC" or "12,345 sats", sub-unit amounts must be whole
+    pub fn parse(value: &str, currencies: &[Arc<Currency>]) -> Result<Self, Error> {
+        let invalid_amount = || Error::InvalidAmount(value.to_string());
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        let (code, number) = match parts.as_slice() {
+            [first, second] if starts_numeric(first) => (*second, *first),
+            [first, second] => (*first, *second),
+            _ => return Err(invalid_amount()),
+        };
+        let amount = Decimal::from_str(&number.replace(',', "")).map_err(|_| invalid_amount())?;
+        let (currency, unit) = currencies
+            .iter()
+            .find_map(|currency| {
+                currency
+                    .get_unit(code)
+                    .or_else(|| code.strip_suffix('s').and_then(|c| currency.get_unit(c)))
+                    .map(|unit| (currency, unit))
+            })
+            .ok_or(Error::UnknownCurrency(code.to_string()))?;
+        if unit.scale > 0 && !amount.fract().is_zero() {
+            return Err(invalid_amount());
+        }
+        let amount = currency
+            .from_unit(amount, &unit.code)
+            .ok_or(Error::UnknownCurrency(code.to_string()))?;
+        Money::new(currency, amount)
+    }
+
+    // amount in a unit of the currency, ie. "12,345 sat"
+    pub fn format_unit(&self, code: &str) -> Option<String> {
+        let unit = self.currency.get_unit(code)?;
+        if unit.scale == 0 {
+            return Some(self.to_string());
+        }
+        let amount = self.currency.to_unit(self.amount, &unit.code)?.normalize();
+        Some(format!(
+            "{} {}",
+            group_digits(&amount.to_string()),
+            unit.code
+        ))
+    }
+
+    // amount in the organization's display unit for the currency
+    pub fn display(&self, organization: &Organization) -> String {
+        organization
+            .display_unit(&self.currency.id)
+            .and_then(|code| self.format_unit(code))
+            .unwrap_or_else(|| self.to_string())
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), Error> {
+        if self.currency.id != other.currency.id {
+            return Err(Error::CurrencyMismatch(self.currency.id, other.currency.id));
+        }
+        Ok(())
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut amount = self.amount;
+        amount.rescale(self.currency.scale.max(amount.scale()));
+        write!(
+            f,
+            "{} {}",
+            self.currency.code,
+            group_digits(&amount.to_string())
+        )
+    }
+}
+
+impl std::convert::From<Money> for CurrencyAmount {
+    fn from(money: Money) -> Self {
+        money.currency_amount()
+    }
+}
+
+fn starts_numeric(value: &str) -> bool {
+    matches!(value.chars().next(), Some(c) if c.is_ascii_digit() || c == '-' || c == '.')
+}
+
+// thousands separators in the integer part of a decimal string
+fn group_digits(value: &str) -> String {
+    let (sign, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", value),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+        None => format!("{}{}", sign, grouped),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::journal::money::{Error, Money, RoundingMode};
+    use crate::journal::{test_entries, EntryType, LedgerEntry};
+    use rust_decimal::Decimal;
+    use rusty_ulid::Ulid;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_money() {
+        let test_entries = test_entries();
+        let currencies: Vec<_> = test_entries
+            .currencies
+            .iter()
+            .cloned()
+            .map(Arc::new)
+            .collect();
+        let usd = currencies.get(0).expect("usd");
+        let btc = currencies.get(1).expect("btc");
+
+        let money = Money::from_minor(usd, 1_234_56);
+        assert_eq!(money.to_string(), "USD 1,234.56");
+        assert_eq!(
+            Money::parse("USD 1,234.56", &currencies).expect("usd"),
+            money
+        );
+        assert_eq!(
+            Money::parse("1234.56 usd", &currencies).expect("usd"),
+            money
+        );
+        let result = Money::new(usd, Decimal::new(1_234_567, 3));
+        assert!(matches!(result, Err(Error::ScaleExceeded(840))));
+        assert_eq!(Money::from_minor(usd, -5).to_string(), "USD -0.05");
+
+        let money = Money::parse("0.00012345 BTC", &currencies).expect("btc");
+        assert_eq!(money, Money::from_minor(btc, 12_345));
+        assert_eq!(money.to_string(), "BTC 0.00012345");
+        assert_eq!(money.format_unit("sat"), Some("12,345 sat".to_string()));
+        assert_eq!(
+            money.format_unit("msat"),
+            Some("12,345,000 msat".to_string())
+        );
+        assert_eq!(money.format_unit("btc"), Some("BTC 0.00012345".to_string()));
+        assert_eq!(
+            Money::parse("12,345 sats", &currencies).expect("sats"),
+            money
+        );
+        assert_eq!(
+            Money::parse("12345000 msat", &currencies).expect("msat"),
+            money
+        );
+        assert!(Money::parse("1.5 sats", &currencies).is_err());
+        assert!(matches!(
+            Money::parse("1 msat", &currencies),
+            Err(Error::ScaleExceeded(2009))
+        ));
+        let msat =
+            Money::with_scale(btc, Decimal::new(1_500, 11), btc.finest_scale()).expect("msat");
+        assert_eq!(msat.to_string(), "BTC 0.00000001500");
+        assert_eq!(msat.format_unit("msat"), Some("1,500 msat".to_string()));
+        let sum = msat.checked_add(&Money::from_minor(btc, 1)).expect("sum");
+        assert_eq!(sum.amount, Decimal::new(2_500, 11));
+        let result = Money::with_scale(btc, Decimal::new(1, 12), btc.finest_scale());
+        assert!(matches!(result, Err(Error::ScaleExceeded(2009))));
+
+        let mut organization = test_entries.organization.clone();
+        assert_eq!(money.display(&organization), "BTC 0.00012345");
+        organization.display_units.insert(btc.id, "sat".to_string());
+        assert_eq!(money.display(&organization), "12,345 sat");
+        assert!(matches!(
+            Money::parse("1 EUR", &currencies),
+            Err(Error::UnknownCurrency(_))
+        ));
+
+        let half_cent = Decimal::new(1_005, 3);
+        assert_eq!(
+            Money::rounded(usd, half_cent, RoundingMode::HalfEven).amount,
+            Decimal::new(1_00, 2)
+        );
+        assert_eq!(
+            Money::rounded(usd, half_cent, RoundingMode::HalfUp).amount,
+            Decimal::new(1_01, 2)
+        );
+
+        let sum = Money::from_minor(usd, 1_00)
+            .checked_add(&Money::from_minor(usd, 50))
+            .expect("sum");
+        assert_eq!(sum, Money::from_minor(usd, 1_50));
+        let result = sum.checked_sub(&Money::from_minor(btc, 1));
+        assert!(matches!(result, Err(Error::CurrencyMismatch(840, 2009))));
+        assert_eq!(
+            sum.multiply(Decimal::new(1, 1), RoundingMode::HalfUp),
+            Money::from_minor(usd, 15)
+        );
+
+        // ledger entries from money have positive amounts at the currency's scale
+        let entry = LedgerEntry::from_money(
+            &Ulid::generate(),
+            &Ulid::generate(),
+            &Money::from_minor(usd, -1_50),
+            None,
+        );
+        assert_eq!(entry.entry_type, EntryType::Credit);
+        assert_eq!(entry.currency_amount.amount, Decimal::new(1_50, 2));
+        assert_eq!(entry.currency_amount.currency_id, usd.id);
+    }
+}
@@ -0,0 +1,972 @@
+use crate::journal::{
+    AccountCategory, AccountId, AccountType, Action, BalanceSheetCategory, CurrencyAmount,
+    CurrencyId, EntryType, LedgerEntry, LotSelection, Transaction, TransactionId, TransactionType,
+};
+use crate::ledger::Ledger;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    MissingAccount(AccountId),
+    MissingCurrency(CurrencyId),
+    MissingPrice(CurrencyId, OffsetDateTime),
+    InsufficientLots(TransactionId),
+    MissingLotSelection(TransactionId),
+    InvalidLotSelection(TransactionId),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingAccount(a) => write!(f, "missing account: {}", a),
+            Self::MissingCurrency(c) => write!(f, "missing currency: {}", c),
+            Self::MissingPrice(c, d) => write!(f, "missing price: {} at {}", c, d),
+            Self::InsufficientLots(t) => write!(f, "insufficient lots: {}", t),
+            Self::MissingLotSelection(t) => write!(f, "missing lot selection: {}", t),
+            Self::InvalidLotSelection(t) => write!(f, "invalid lot selection: {}", t),
+        }
+    }
+}
+
+/// Price of one unit of a currency in a quote currency at a date time
+pub trait PriceSource {
+    fn price(
+        &self,
+        currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        datetime: &OffsetDateTime,
+    ) -> Option<Decimal>;
+}
+
+/// Price history, the price at a date time is the most recent price at or before it
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Default)]
+pub struct PriceHistory {
+    prices: BTreeMap<(CurrencyId, CurrencyId), BTreeMap<OffsetDateTime, Decimal>>,
+}
+
+impl PriceHistory {
+    pub fn new() -> Self {
+        PriceHistory::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        datetime: OffsetDateTime,
+        price: Decimal,
+    ) {
+        self.prices
+            .entry((*currency_id, *quote_currency_id))
+            .or_default()
+            .insert(datetime, price);
+    }
+}
+
+impl PriceSource for PriceHistory {
+    fn price(
+        &self,
+        currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        datetime: &OffsetDateTime,
+    ) -> Option<Decimal> {
+        self.prices
+            .get(&(*currency_id, *quote_currency_id))
+            .and_then(|prices| prices.range(..=*datetime).next_back())
+            .map(|(_, price)| *price)
+    }
+}
+
+impl PriceSource for Ledger {
+    fn price(
+        &self,
+        currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        datetime: &OffsetDateTime,
+    ) -> Option<Decimal> {
+        self.exchange_rate(currency_id, quote_currency_id, datetime)
+    }
+}
+
+/// Order lots are disposed of in
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum CostBasisMethod {
+    Fifo,
+    Lifo,
+    Hifo,
+    SpecificIdentification {
+        selections: BTreeMap<TransactionId, Vec<LotSelection>>,
+    },
+}
+
+/// Amount acquired by a debit entry and its unit cost in the quote currency
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Lot {
+    pub lot_id: TransactionId,
+    pub datetime: OffsetDateTime,
+    pub amount: Decimal,
+    pub remaining: Decimal,
+    pub unit_cost: Decimal,
+}
+
+impl Lot {
+    pub fn cost(&self) -> Decimal {
+        self.remaining * self.unit_cost
+    }
+}
+
+/// Portion of a lot disposed of and its cost
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct LotDisposal {
+    pub lot_id: TransactionId,
+    pub amount: Decimal,
+    pub cost: Decimal,
+}
+
+/// Amount disposed of by a credit entry, its proceeds and the cost of lots disposed of, lot
+/// selections are all lots selected by the entry including any transferred
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Disposal {
+    pub transaction_id: TransactionId,
+    pub datetime: OffsetDateTime,
+    pub amount: Decimal,
+    pub proceeds: Decimal,
+    pub cost: Decimal,
+    pub lot_disposals: Vec<LotDisposal>,
+    pub lot_selections: Vec<LotSelection>,
+}
+
+impl Disposal {
+    pub fn gain(&self) -> Decimal {
+        self.proceeds - self.cost
+    }
+}
+
+/// Lots moved by a transfer to another of the organization's asset accounts, at their original
+/// cost and without a gain
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Transfer {
+    pub transaction_id: TransactionId,
+    pub datetime: OffsetDateTime,
+    pub account_id: AccountId,
+    pub lots: Vec<Lot>,
+}
+
+/// Open lots, disposals and transfers out of one currency in an account, costs in a quote currency
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct CostBasis {
+    pub account_id: AccountId,
+    pub currency_id: CurrencyId,
+    pub quote_currency_id: CurrencyId,
+    pub date_time: OffsetDateTime,
+    pub lots: Vec<Lot>,
+    pub disposals: Vec<Disposal>,
+    pub transfers: Vec<Transfer>,
+}
+
+impl CostBasis {
+    // lots and disposals from account entries at or before date time, priced at transaction times,
+    // transfers to or from the organization's other asset accounts carry lots over at their cost
+    pub fn new<P: PriceSource>(
+        ledger: &Ledger,
+        account_id: &AccountId,
+        currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        method: &CostBasisMethod,
+        prices: &P,
+        date_time: OffsetDateTime,
+    ) -> Result<Self, Error> {
+        CostBasis::new_until(
+            ledger,
+            account_id,
+            currency_id,
+            quote_currency_id,
+            method,
+            prices,
+            date_time,
+            None,
+        )
+    }
+
+    // cost basis from entries up to and including the until transaction if one is given, the
+    // lots a transfer carries over are found from the sending account's cost basis until it
+    #[allow(clippy::too_many_arguments)]
+    fn new_until<P: PriceSource>(
+        ledger: &Ledger,
+        account_id: &AccountId,
+        currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        method: &CostBasisMethod,
+        prices: &P,
+        date_time: OffsetDateTime,
+        until: Option<(OffsetDateTime, TransactionId)>,
+    ) -> Result<Self, Error> {
+        ledger
+            .get_account(account_id)
+            .ok_or(Error::MissingAccount(*account_id))?;
+        let mut entries: Vec<(Transaction, LedgerEntry)> = ledger
+            .get_account_entries_period(account_id, None, &date_time)
+            .iter()
+            .filter(|entry| &entry.currency_amount.currency_id == currency_id)
+            .filter_map(|entry| {
+                ledger
+                    .get_transaction(&entry.transaction_id)
+                    .map(|transaction| (transaction.as_ref().clone(), entry.as_ref().clone()))
+            })
+            .filter(|(transaction, _)| {
+                until.is_none_or(|until| (transaction.datetime, transaction.id) <= until)
+            })
+            .collect();
+        entries.sort_by_key(|(transaction, _)| (transaction.datetime, transaction.id));
+        let price = |transaction: &Transaction| {
+            prices
+                .price(currency_id, quote_currency_id, &transaction.datetime)
+                .ok_or(Error::MissingPrice(*currency_id, transaction.datetime))
+        };
+
+        let recorded = recorded_selections(ledger);
+
+        let mut lots: Vec<Lot> = Vec::new();
+        let mut disposals = Vec::new();
+        let mut transfers = Vec::new();
+        for (transaction, entry) in entries {
+            let amount = entry.currency_amount.amount;
+            let transfer_entries = transfer_entries(ledger, &entry);
+            match entry.entry_type {
+                EntryType::Debit => {
+                    // lots carried over from the sending accounts, any other amount is a new lot
+                    let mut carried = Decimal::ZERO;
+                    for sender_entry in transfer_entries {
+                        let sender = CostBasis::new_until(
+                            ledger,
+                            &sender_entry.account_id,
+                            currency_id,
+                            quote_currency_id,
+                            method,
+                            prices,
+                            transaction.datetime,
+                            Some((transaction.datetime, transaction.id)),
+                        )?;
+                        let carried_lots = sender
+                            .transfers
+                            .into_iter()
+                            .filter(|transfer| {
+                                transfer.transaction_id == transaction.id
+                                    && &transfer.account_id == account_id
+                            })
+                            .flat_map(|transfer| transfer.lots);
+                        for lot in carried_lots {
+                            carried += lot.amount;
+                            add_lot(&mut lots, lot);
+                        }
+                    }
+                    if amount > carried {
+                        add_lot(
+                            &mut lots,
+                            Lot {
+                                lot_id: transaction.id,
+                                datetime: transaction.datetime,
+                                amount: amount - carried,
+                                remaining: amount - carried,
+                                unit_cost: price(&transaction)?,
+                            },
+                        );
+                    }
+                }
+                EntryType::Credit => {
+                    // lots moved to the receiving accounts first, the rest is disposed of
+                    let mut selections =
+                        select_lots(&lots, method, &recorded, &transaction.id, amount)?;
+                    let lot_selections = selections.clone();
+                    for receiver_entry in transfer_entries {
+                        let moved = split_selections(
+                            &mut selections,
+                            receiver_entry.currency_amount.amount,
+                        );
+                        transfers.push(Transfer {
+                            transaction_id: transaction.id,
+                            datetime: transaction.datetime,
+                            account_id: receiver_entry.account_id,
+                            lots: take_lots(&mut lots, moved, &transaction.id)?,
+                        });
+                    }
+                    if selections.is_empty() {
+                        continue;
+                    }
+                    let disposed: Decimal =
+                        selections.iter().map(|selection| selection.amount).sum();
+                    let lot_disposals: Vec<LotDisposal> =
+                        take_lots(&mut lots, selections, &transaction.id)?
+                            .iter()
+                            .map(|lot| LotDisposal {
+                                lot_id: lot.lot_id,
+                                amount: lot.amount,
+                                cost: lot.cost(),
+                            })
+                            .collect();
+                    let cost = lot_disposals.iter().map(|lot| lot.cost).sum();
+                    disposals.push(Disposal {
+                        transaction_id: transaction.id,
+                        datetime: transaction.datetime,
+                        amount: disposed,
+                        proceeds: disposed * price(&transaction)?,
+                        cost,
+                        lot_disposals,
+                        lot_selections,
+                    });
+                }
+            }
+        }
+        lots.retain(|lot| !lot.remaining.is_zero());
+
+        Ok(CostBasis {
+            account_id: *account_id,
+            currency_id: *currency_id,
+            quote_currency_id: *quote_currency_id,
+            date_time,
+            lots,
+            disposals,
+            transfers,
+        })
+    }
+
+    pub fn cost(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.cost()).sum()
+    }
+
+    pub fn realized_gain(&self) -> Decimal {
+        self.disposals.iter().map(|disposal| disposal.gain()).sum()
+    }
+
+    // realized gain transactions for disposals without one, gains credited to the gain account
+    // and offset in the cost basis account
+    pub fn realized_gain_actions(
+        &self,
+        ledger: &Ledger,
+        gain_account_id: &AccountId,
+        basis_account_id: &AccountId,
+    ) -> Result<Vec<Action>, Error> {
+        ledger
+            .get_account(gain_account_id)
+            .ok_or(Error::MissingAccount(*gain_account_id))?;
+        ledger
+            .get_account(basis_account_id)
+            .ok_or(Error::MissingAccount(*basis_account_id))?;
+        let scale = ledger
+            .get_currency(&self.quote_currency_id)
+            .ok_or(Error::MissingCurrency(self.quote_currency_id))?
+            .scale;
+        let recorded: Vec<TransactionId> = ledger
+            .transactions()
+            .iter()
+            .filter_map(|transaction| match transaction.transaction_type {
+                TransactionType::RealizedGain { transaction_id, .. } => Some(transaction_id),
+                _ => None,
+            })
+            .collect();
+
+        let actions = self
+            .disposals
+            .iter()
+            .filter(|disposal| !recorded.contains(&disposal.transaction_id))
+            .filter(|disposal| !disposal.gain().round_dp(scale).is_zero())
+            .map(|disposal| {
+                let gain = disposal.gain().round_dp(scale);
+                let transaction = Transaction::new(
+                    disposal.datetime,
+                    format!("Realized gain {}", disposal.transaction_id),
+                    TransactionType::RealizedGain {
+                        transaction_id: disposal.transaction_id,
+                        lot_selections: disposal.lot_selections.clone(),
+                    },
+                );
+                let amount = CurrencyAmount::new(&self.quote_currency_id, gain.abs());
+                let (basis_entry_type, gain_entry_type) = if gain.is_sign_positive() {
+                    (EntryType::Debit, EntryType::Credit)
+                } else {
+                    (EntryType::Credit, EntryType::Debit)
+                };
+                let ledger_entries = vec![
+                    LedgerEntry::new(
+                        &transaction.id,
+                        basis_entry_type,
+                        basis_account_id,
+                        amount.clone(),
+                        Some("Cost basis adjustment".to_string()),
+                    ),
+                    LedgerEntry::new(
+                        &transaction.id,
+                        gain_entry_type,
+                        gain_account_id,
+                        amount,
+                        Some("Realized gain".to_string()),
+                    ),
+                ];
+                Action::AddTransaction {
+                    transaction,
+                    ledger_entries,
+                }
+            })
+            .collect();
+        Ok(actions)
+    }
+}
+
+/// Open lots valued at the price at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct UnrealizedGainReport {
+    pub date_time: OffsetDateTime,
+    pub price: Decimal,
+    pub lots: Vec<Lot>,
+    pub amount: Decimal,
+    pub cost: Decimal,
+    pub market_value: Decimal,
+    pub unrealized_gain: Decimal,
+}
+
+impl UnrealizedGainReport {
+    pub fn new<P: PriceSource>(cost_basis: &CostBasis, prices: &P) -> Result<Self, Error> {
+        let date_time = cost_basis.date_time;
+        let price = prices
+            .price(
+                &cost_basis.currency_id,
+                &cost_basis.quote_currency_id,
+                &date_time,
+            )
+            .ok_or(Error::MissingPrice(cost_basis.currency_id, date_time))?;
+        let lots = cost_basis.lots.clone();
+        let amount: Decimal = lots.iter().map(|lot| lot.remaining).sum();
+        let cost = cost_basis.cost();
+        let market_value = amount * price;
+        Ok(UnrealizedGainReport {
+            date_time,
+            price,
+            lots,
+            amount,
+            cost,
+            market_value,
+            unrealized_gain: market_value - cost,
+        })
+    }
+}
+
+// lots selected for the disposed amount, selections recorded with a realized gain are kept
+// whatever the method, otherwise lots are selected in the method's order
+fn select_lots(
+    lots: &[Lot],
+    method: &CostBasisMethod,
+    recorded: &BTreeMap<TransactionId, Vec<LotSelection>>,
+    transaction_id: &TransactionId,
+    amount: Decimal,
+) -> Result<Vec<LotSelection>, Error> {
+    let selections = match (recorded.get(transaction_id), method) {
+        (Some(selections), _) => selections,
+        (None, CostBasisMethod::SpecificIdentification { selections }) => selections
+            .get(transaction_id)
+            .ok_or(Error::MissingLotSelection(*transaction_id))?,
+        (None, _) => {
+            let mut open: Vec<&Lot> = lots.iter().filter(|lot| !lot.remaining.is_zero()).collect();
+            match method {
+                CostBasisMethod::Lifo => open.reverse(),
+                CostBasisMethod::Hifo => open.sort_by_key(|lot| Reverse(lot.unit_cost)),
+                _ => {}
+            }
+            let mut remaining = amount;
+            let mut selections = Vec::new();
+            for lot in open {
+                if remaining.is_zero() {
+                    break;
+                }
+                let lot_amount = remaining.min(lot.remaining);
+                remaining -= lot_amount;
+                selections.push(LotSelection {
+                    lot_id: lot.lot_id,
+                    amount: lot_amount,
+                });
+            }
+            if !remaining.is_zero() {
+                return Err(Error::InsufficientLots(*transaction_id));
+            }
+            return Ok(selections);
+        }
+    };
+    let selected: Decimal = selections.iter().map(|selection| selection.amount).sum();
+    if selected != amount {
+        return Err(Error::InvalidLotSelection(*transaction_id));
+    }
+    Ok(selections.clone())
+}
+
+// lot selections recorded with realized gains by disposing transaction
+fn recorded_selections(ledger: &Ledger) -> BTreeMap<TransactionId, Vec<LotSelection>> {
+    ledger
+        .transactions()
+        .iter()
+        .filter_map(|transaction| match &transaction.transaction_type {
+            TransactionType::RealizedGain {
+                transaction_id,
+                lot_selections,
+            } if !lot_selections.is_empty() => Some((*transaction_id, lot_selections.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+// selections of up to amount taken from the front of the selections
+fn split_selections(selections: &mut Vec<LotSelection>, amount: Decimal) -> Vec<LotSelection> {
+    let mut remaining = amount;
+    let mut split = Vec::new();
+    while remaining > Decimal::ZERO && !selections.is_empty() {
+        let selection = &mut selections[0];
+        let split_amount = remaining.min(selection.amount);
+        remaining -= split_amount;
+        selection.amount -= split_amount;
+        split.push(LotSelection {
+            lot_id: selection.lot_id,
+            amount: split_amount,
+        });
+        if selection.amount.is_zero() {
+            selections.remove(0);
+        }
+    }
+    split
+}
+
+// reduce open lots by the selected amounts, returning the portions taken at their cost
+fn take_lots(
+    lots: &mut [Lot],
+    selections: Vec<LotSelection>,
+    transaction_id: &TransactionId,
+) -> Result<Vec<Lot>, Error> {
+    let mut taken = Vec::new();
+    for selection in selections {
+        let lot = lots
+            .iter_mut()
+            .find(|lot| lot.lot_id == selection.lot_id && lot.remaining >= selection.amount)
+            .ok_or(Error::InvalidLotSelection(*transaction_id))?;
+        lot.remaining -= selection.amount;
+        taken.push(Lot {
+            amount: selection.amount,
+            remaining: selection.amount,
+            ..lot.clone()
+        });
+    }
+    Ok(taken)
+}
+
+// add to a lot with the same id and unit cost, ie. a lot carried over by more than one transfer
+fn add_lot(lots: &mut Vec<Lot>, lot: Lot) {
+    match lots
+        .iter_mut()
+        .find(|open| open.lot_id == lot.lot_id && open.unit_cost == lot.unit_cost)
+    {
+        Some(open) => {
+            open.amount += lot.amount;
+            open.remaining += lot.remaining;
+        }
+        None => lots.push(lot),
+    }
+}
+
+// entries of the same currency and opposite type in the organization's other asset accounts,
+// excluding contact accounts
+fn transfer_entries(ledger: &Ledger, entry: &LedgerEntry) -> Vec<Arc<LedgerEntry>> {
+    ledger
+        .get_transaction_entries(&entry.transaction_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|other| {
+            other.account_id != entry.account_id
+                && other.entry_type != entry.entry_type
+                && other.currency_amount.currency_id == entry.currency_amount.currency_id
+                && ledger
+                    .get_account(&other.account_id)
+                    .is_some_and(|account| {
+                        account.account_category
+                            == AccountCategory::BalanceSheet(BalanceSheetCategory::Asset)
+                            && !matches!(account.account_type, AccountType::ContactAccount { .. })
+                    })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::journal::Action::{AddAccount, AddTransaction};
+    use crate::journal::{
+        test_entries, Account, AccountCategory, AccountType, BalanceSheetCategory, CurrencyAmount,
+        EntryType, IncomeStatementCategory, JournalEntry, LedgerEntry, LotSelection, Transaction,
+        TransactionType,
+    };
+    use crate::ledger::cost_basis::{
+        CostBasis, CostBasisMethod, Error, PriceHistory, UnrealizedGainReport,
+    };
+    use crate::ledger::test::setup;
+    use crate::ledger::OrganizationLedgers;
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+    use time::macros::datetime;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn test_cost_basis() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let btc_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Bitcoin Holdings".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        let basis_account = Account::new(
+            Some(&assets.id),
+            301,
+            "Bitcoin Cost Basis".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        let gain_account = Account::new(
+            None,
+            600,
+            "Realized Gains".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::IncomeStatement(IncomeStatementCategory::NonOperatingRevenue),
+        );
+        for account in [&btc_account, &basis_account, &gain_account] {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddAccount {
+                        account: account.clone(),
+                    },
+                ))
+                .expect("add account");
+        }
+
+        // buy 1 BTC at 40,000 and 1 BTC at 30,000 then sell 1.5 BTC at 50,000
+        let mut transfer = |datetime: OffsetDateTime, entry_type: EntryType, amount: Decimal| {
+            let transaction = Transaction::new(
+                datetime,
+                "Bitcoin transfer".to_string(),
+                TransactionType::LedgerAdjustment,
+            );
+            let currency_amount = CurrencyAmount::new(&btc_id, amount);
+            let (owner_entry_type, btc_entry_type) = match entry_type {
+                EntryType::Debit => (EntryType::Credit, EntryType::Debit),
+                EntryType::Credit => (EntryType::Debit, EntryType::Credit),
+            };
+            let ledger_entries = vec![
+                LedgerEntry::new(
+                    &transaction.id,
+                    btc_entry_type,
+                    &btc_account.id,
+                    currency_amount.clone(),
+                    None,
+                ),
+                LedgerEntry::new(
+                    &transaction.id,
+                    owner_entry_type,
+                    &owner_account_id,
+                    currency_amount,
+                    None,
+                ),
+            ];
+            let transaction_id = transaction.id;
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddTransaction {
+                        transaction,
+                        ledger_entries,
+                    },
+                ))
+                .expect("add transfer");
+            transaction_id
+        };
+        let first_lot_id = transfer(
+            datetime!(2022-03-01 09:00 UTC),
+            EntryType::Debit,
+            Decimal::ONE,
+        );
+        let second_lot_id = transfer(
+            datetime!(2022-04-01 09:00 UTC),
+            EntryType::Debit,
+            Decimal::ONE,
+        );
+        let sale_id = transfer(
+            datetime!(2022-05-01 09:00 UTC),
+            EntryType::Credit,
+            Decimal::new(15, 1),
+        );
+
+        let mut prices = PriceHistory::new();
+        prices.insert(
+            &btc_id,
+            &usd_id,
+            datetime!(2022-03-01 00:00 UTC),
+            Decimal::new(40_000, 0),
+        );
+        prices.insert(
+            &btc_id,
+            &usd_id,
+            datetime!(2022-04-01 00:00 UTC),
+            Decimal::new(30_000, 0),
+        );
+        prices.insert(
+            &btc_id,
+            &usd_id,
+            datetime!(2022-05-01 00:00 UTC),
+            Decimal::new(50_000, 0),
+        );
+        prices.insert(
+            &btc_id,
+            &usd_id,
+            datetime!(2022-06-01 00:00 UTC),
+            Decimal::new(20_000, 0),
+        );
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let date_time = datetime!(2022-06-30 00:00 UTC);
+        let cost_basis = |method: CostBasisMethod| {
+            CostBasis::new(
+                &ledger,
+                &btc_account.id,
+                &btc_id,
+                &usd_id,
+                &method,
+                &prices,
+                date_time,
+            )
+        };
+
+        let fifo = cost_basis(CostBasisMethod::Fifo).expect("fifo");
+        assert_eq!(fifo.realized_gain(), Decimal::new(20_000, 0));
+        assert_eq!(fifo.lots.len(), 1);
+        assert_eq!(fifo.lots[0].lot_id, second_lot_id);
+        assert_eq!(fifo.cost(), Decimal::new(15_000, 0));
+
+        let lifo = cost_basis(CostBasisMethod::Lifo).expect("lifo");
+        assert_eq!(lifo.realized_gain(), Decimal::new(25_000, 0));
+        assert_eq!(lifo.lots[0].lot_id, first_lot_id);
+
+        let hifo = cost_basis(CostBasisMethod::Hifo).expect("hifo");
+        assert_eq!(hifo.realized_gain(), Decimal::new(20_000, 0));
+        assert_eq!(hifo.lots[0].lot_id, second_lot_id);
+
+        let mut selections = BTreeMap::new();
+        selections.insert(
+            sale_id,
+            vec![
+                LotSelection {
+                    lot_id: first_lot_id,
+                    amount: Decimal::new(5, 1),
+                },
+                LotSelection {
+                    lot_id: second_lot_id,
+                    amount: Decimal::ONE,
+                },
+            ],
+        );
+        let specific = cost_basis(CostBasisMethod::SpecificIdentification { selections })
+            .expect("specific identification");
+        assert_eq!(specific.realized_gain(), Decimal::new(25_000, 0));
+        let result = cost_basis(CostBasisMethod::SpecificIdentification {
+            selections: BTreeMap::new(),
+        });
+        assert!(matches!(result, Err(Error::MissingLotSelection(_))));
+
+        let unrealized = UnrealizedGainReport::new(&fifo, &prices).expect("unrealized");
+        assert_eq!(unrealized.price, Decimal::new(20_000, 0));
+        assert_eq!(unrealized.amount, Decimal::new(5, 1));
+        assert_eq!(unrealized.unrealized_gain, Decimal::new(-5_000, 0));
+
+        let actions = fifo
+            .realized_gain_actions(&ledger, &gain_account.id, &basis_account.id)
+            .expect("realized gain actions");
+        assert_eq!(actions.len(), 1);
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add realized gain");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let actions = fifo
+            .realized_gain_actions(&ledger, &gain_account.id, &basis_account.id)
+            .expect("realized gain actions");
+        assert!(actions.is_empty());
+
+        // lots recorded with the realized gain are kept when the method changes
+        let lifo = CostBasis::new(
+            &ledger,
+            &btc_account.id,
+            &btc_id,
+            &usd_id,
+            &CostBasisMethod::Lifo,
+            &prices,
+            date_time,
+        )
+        .expect("lifo");
+        assert_eq!(lifo.realized_gain(), fifo.realized_gain());
+        assert_eq!(lifo.lots, fifo.lots);
+    }
+
+    #[test]
+    fn test_cost_basis_transfer() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let expenses_account_id = test_entries.accounts.get(4).expect("expenses").id;
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let hot_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Bitcoin Hot Wallet".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        let cold_account = Account::new(
+            Some(&assets.id),
+            301,
+            "Bitcoin Cold Storage".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        for account in [&hot_account, &cold_account] {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddAccount {
+                        account: account.clone(),
+                    },
+                ))
+                .expect("add account");
+        }
+
+        // buy 1 BTC at 40,000 and 1 BTC at 30,000 then move 1.2 BTC to cold storage paying a
+        // 0.001 BTC fee at 50,000
+        let mut add_transaction = |datetime: OffsetDateTime, entries: Vec<(EntryType, _, i64)>| {
+            let transaction = Transaction::new(
+                datetime,
+                "Bitcoin transfer".to_string(),
+                TransactionType::LedgerAdjustment,
+            );
+            let ledger_entries = entries
+                .into_iter()
+                .map(|(entry_type, account_id, amount)| {
+                    LedgerEntry::new(
+                        &transaction.id,
+                        entry_type,
+                        account_id,
+                        CurrencyAmount::new(&btc_id, Decimal::new(amount, 3)),
+                        None,
+                    )
+                })
+                .collect();
+            let transaction_id = transaction.id;
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddTransaction {
+                        transaction,
+                        ledger_entries,
+                    },
+                ))
+                .expect("add transaction");
+            transaction_id
+        };
+        let first_lot_id = add_transaction(
+            datetime!(2022-03-01 09:00 UTC),
+            vec![
+                (EntryType::Debit, &hot_account.id, 1_000),
+                (EntryType::Credit, &owner_account_id, 1_000),
+            ],
+        );
+        let second_lot_id = add_transaction(
+            datetime!(2022-04-01 09:00 UTC),
+            vec![
+                (EntryType::Debit, &hot_account.id, 1_000),
+                (EntryType::Credit, &owner_account_id, 1_000),
+            ],
+        );
+        let transfer_id = add_transaction(
+            datetime!(2022-05-01 09:00 UTC),
+            vec![
+                (EntryType::Credit, &hot_account.id, 1_201),
+                (EntryType::Debit, &cold_account.id, 1_200),
+                (EntryType::Debit, &expenses_account_id, 1),
+            ],
+        );
+
+        let mut prices = PriceHistory::new();
+        for (datetime, price) in [
+            (datetime!(2022-03-01 00:00 UTC), 40_000),
+            (datetime!(2022-04-01 00:00 UTC), 30_000),
+            (datetime!(2022-05-01 00:00 UTC), 50_000),
+        ] {
+            prices.insert(&btc_id, &usd_id, datetime, Decimal::new(price, 0));
+        }
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let cost_basis = |account_id| {
+            CostBasis::new(
+                &ledger,
+                account_id,
+                &btc_id,
+                &usd_id,
+                &CostBasisMethod::Fifo,
+                &prices,
+                datetime!(2022-06-30 00:00 UTC),
+            )
+        };
+
+        // only the fee is a disposal, the transferred lots keep their cost
+        let hot = cost_basis(&hot_account.id).expect("hot wallet");
+        assert_eq!(hot.disposals.len(), 1);
+        assert_eq!(hot.disposals[0].transaction_id, transfer_id);
+        assert_eq!(hot.disposals[0].amount, Decimal::new(1, 3));
+        assert_eq!(hot.realized_gain(), Decimal::new(20, 0));
+        assert_eq!(hot.transfers.len(), 1);
+        assert_eq!(hot.transfers[0].account_id, cold_account.id);
+        assert_eq!(hot.cost(), Decimal::new(23_970, 0));
+
+        let cold = cost_basis(&cold_account.id).expect("cold storage");
+        assert!(cold.disposals.is_empty());
+        assert_eq!(cold.lots.len(), 2);
+        assert_eq!(cold.lots[0].lot_id, first_lot_id);
+        assert_eq!(cold.lots[0].remaining, Decimal::ONE);
+        assert_eq!(cold.lots[1].lot_id, second_lot_id);
+        assert_eq!(cold.lots[1].remaining, Decimal::new(2, 1));
+        assert_eq!(cold.cost(), Decimal::new(46_000, 0));
+    }
+}
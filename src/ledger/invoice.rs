@@ -0,0 +1,1798 @@
+use crate::journal::money::{Money, RoundingMode};
+use crate::journal::BalanceSheetCategory::{Asset, Liability};
+use crate::journal::{
+    AccountCategory, AccountId, AccountType, Action, ContactId, CurrencyAmount, CurrencyId,
+    EntryType, LedgerEntry, LineItem, Payment, PaymentSchedule, Transaction, TransactionId,
+    TransactionType,
+};
+use crate::ledger::{payment_amount, Error, Ledger};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// Accounts receivable status of an invoice at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InvoiceStatus {
+    Draft,
+    Issued,
+    PartiallyPaid,
+    Paid,
+    Overdue,
+    WrittenOff,
+}
+
+/// Invoices debit a contact's asset account for receivables or credit a contact's liability
+/// account for payables
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BalanceType {
+    Receivable,
+    Payable,
+}
+
+impl BalanceType {
+    // entry amount that increases the balance owed
+    pub fn amount(&self, entry: &LedgerEntry) -> Decimal {
+        match (self, &entry.entry_type) {
+            (BalanceType::Receivable, EntryType::Debit)
+            | (BalanceType::Payable, EntryType::Credit) => entry.currency_amount.amount,
+            _ => -entry.currency_amount.amount,
+        }
+    }
+}
+
+/// Receivable or payable amount of an invoice and its payments, write offs, late fees and
+/// discounts at a date time, with the discount available and late fee interest not yet accrued
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct InvoiceBalance {
+    pub transaction_id: TransactionId,
+    pub balance_type: BalanceType,
+    pub contact_id: Option<ContactId>,
+    pub contact_account_id: Option<AccountId>,
+    pub date_time: OffsetDateTime,
+    pub due_date_time: OffsetDateTime,
+    pub currency_id: CurrencyId,
+    pub amount: Decimal,
+    pub paid: Decimal,
+    pub written_off: Decimal,
+    pub late_fees: Decimal,
+    pub discounts: Decimal,
+    pub outstanding: Decimal,
+    pub discount_available: Decimal,
+    pub late_fee_due: Decimal,
+    pub status: InvoiceStatus,
+}
+
+impl InvoiceBalance {
+    // invoices dated after date time are drafts, voided or reversed invoices are not valid
+    pub fn new(
+        ledger: &Ledger,
+        transaction_id: &TransactionId,
+        date_time: OffsetDateTime,
+    ) -> Result<Self, Error> {
+        let invoice = ledger
+            .get_transaction(transaction_id)
+            .ok_or(Error::MissingTransaction(*transaction_id))?;
+        let payment_terms = match &invoice.transaction_type {
+            TransactionType::Invoice { payment_terms, .. }
+            | TransactionType::Bill { payment_terms, .. } => payment_terms,
+            _ => return Err(Error::InvalidInvoice(*transaction_id)),
+        };
+        if ledger.get_reversal(transaction_id).is_some()
+            || ledger.get_void(transaction_id).is_some()
+        {
+            return Err(Error::InvalidInvoice(*transaction_id));
+        }
+
+        let (balance_type, contact_account_ids) = contact_account_ids(ledger, transaction_id);
+        let invoice_entries = ledger
+            .get_transaction_entries(transaction_id)
+            .unwrap_or_default();
+        let currency_id = invoice_entries
+            .iter()
+            .find(|entry| contact_account_ids.contains(&entry.account_id))
+            .or_else(|| invoice_entries.first())
+            .map(|entry| entry.currency_amount.currency_id)
+            .ok_or(Error::MissingLedgerEntries(*transaction_id))?;
+        let contact_id = contact_account_ids
+            .iter()
+            .filter_map(|account_id| ledger.get_account(account_id))
+            .find_map(|account| match account.account_type {
+                AccountType::ContactAccount { contact_id } => Some(contact_id),
+                _ => None,
+            });
+
+        // change in the balance owed by a transaction
+        let owed = |transaction: &Transaction| -> Decimal {
+            ledger
+                .get_transaction_entries(&transaction.id)
+                .iter()
+                .flatten()
+                .filter(|entry| {
+                    contact_account_ids.contains(&entry.account_id)
+                        && entry.currency_amount.currency_id == currency_id
+                })
+                .map(|entry| balance_type.amount(entry))
+                .sum()
+        };
+        let amount = owed(&invoice);
+        let mut paid = Decimal::ZERO;
+        let mut written_off = Decimal::ZERO;
+        let mut late_fees = Decimal::ZERO;
+        let mut discounts = Decimal::ZERO;
+        let mut principal_changes = Vec::new();
+        let mut last_late_fee_date = None;
+        for transaction in ledger.get_invoice_payments(transaction_id) {
+            if !posted(ledger, &transaction, &date_time) {
+                continue;
+            }
+            let change = owed(&transaction);
+            match transaction.transaction_type {
+                TransactionType::LateFee { .. } => {
+                    late_fees += change;
+                    last_late_fee_date = last_late_fee_date.max(Some(transaction.datetime.date()));
+                    continue;
+                }
+                TransactionType::WriteOff { .. } => written_off -= change,
+                TransactionType::Discount { .. } => discounts -= change,
+                _ => paid -= change,
+            }
+            principal_changes.push((transaction.datetime.date(), change));
+        }
+        principal_changes.sort();
+        let outstanding = amount + late_fees - paid - discounts - written_off;
+        let due_date_time = payment_terms.due_date_time(invoice.datetime);
+
+        // discounts apply to the invoice amount and late fee interest to the unpaid invoice
+        // amount, excluding late fees, accrued daily since the due date or the last late fee
+        let scale = ledger
+            .get_currency(&currency_id)
+            .ok_or(Error::MissingCurrency(currency_id))?
+            .scale;
+        let principal = amount - paid - discounts - written_off;
+        let discount_available = match payment_terms.discount_date_time(invoice.datetime) {
+            Some(discount_date_time)
+                if invoice.datetime <= date_time
+                    && date_time <= discount_date_time
+                    && principal > Decimal::ZERO =>
+            {
+                (amount * payment_terms.discount())
+                    .round_dp(scale)
+                    .min(principal)
+            }
+            _ => Decimal::ZERO,
+        };
+        let days_overdue = (date_time.date() - due_date_time.date()).whole_days();
+        let late_fee_due = if days_overdue > 0 && principal > Decimal::ZERO {
+            let mut from = last_late_fee_date
+                .unwrap_or(due_date_time.date())
+                .max(due_date_time.date());
+            let mut unpaid = amount;
+            let mut unpaid_days = Decimal::ZERO;
+            for (date, change) in principal_changes {
+                if date > from {
+                    unpaid_days +=
+                        unpaid.max(Decimal::ZERO) * Decimal::from((date - from).whole_days());
+                    from = date;
+                }
+                unpaid += change;
+            }
+            unpaid_days +=
+                unpaid.max(Decimal::ZERO) * Decimal::from((date_time.date() - from).whole_days());
+            (unpaid_days * payment_terms.late_fee_interest() / Decimal::from(365)).round_dp(scale)
+        } else {
+            Decimal::ZERO
+        };
+
+        let status = if date_time < invoice.datetime {
+            InvoiceStatus::Draft
+        } else if outstanding <= Decimal::ZERO && !written_off.is_zero() {
+            InvoiceStatus::WrittenOff
+        } else if outstanding <= Decimal::ZERO {
+            InvoiceStatus::Paid
+        } else if days_overdue > 0 {
+            // overdue the day after the due date, so immediate payment terms are not overdue
+            // the moment they are issued
+            InvoiceStatus::Overdue
+        } else if paid > Decimal::ZERO {
+            InvoiceStatus::PartiallyPaid
+        } else {
+            InvoiceStatus::Issued
+        };
+
+        Ok(InvoiceBalance {
+            transaction_id: *transaction_id,
+            balance_type,
+            contact_id,
+            contact_account_id: contact_account_ids.first().copied(),
+            date_time,
+            due_date_time,
+            currency_id,
+            amount,
+            paid,
+            written_off,
+            late_fees,
+            discounts,
+            outstanding,
+            discount_available,
+            late_fee_due,
+            status,
+        })
+    }
+
+    // payment at the balance date time, of the outstanding amount less the discount available if
+    // the payment has no amount, and the discount taken if the payment settles the invoice
+    pub fn payment_actions(
+        &self,
+        payment: Payment,
+        payment_account_id: &AccountId,
+        discount_account_id: &AccountId,
+    ) -> Result<Vec<Action>, Error> {
+        let contact_account_id = self
+            .contact_account_id
+            .ok_or(Error::InvalidInvoice(self.transaction_id))?;
+        let amount = payment_amount(&payment)
+            .map(|currency_amount| currency_amount.amount)
+            .unwrap_or(self.outstanding - self.discount_available);
+        let discount = if amount >= self.outstanding - self.discount_available {
+            (self.outstanding - amount)
+                .min(self.discount_available)
+                .max(Decimal::ZERO)
+        } else {
+            Decimal::ZERO
+        };
+
+        let description = match self.balance_type {
+            BalanceType::Receivable => "Invoice payment",
+            BalanceType::Payable => "Bill payment",
+        };
+        let transaction = Transaction::new(
+            self.date_time,
+            description.to_string(),
+            TransactionType::InvoicePayment {
+                transaction_id: self.transaction_id,
+            },
+        );
+        let ledger_entries = self.entries(
+            &transaction.id,
+            &contact_account_id,
+            payment_account_id,
+            -amount,
+        );
+        let mut actions = vec![Action::AddPayment {
+            transaction_id: self.transaction_id,
+            payment,
+            transaction,
+            ledger_entries,
+        }];
+        if !discount.is_zero() {
+            let transaction = Transaction::new(
+                self.date_time,
+                "Early payment discount".to_string(),
+                TransactionType::Discount {
+                    transaction_id: self.transaction_id,
+                },
+            );
+            let ledger_entries = self.entries(
+                &transaction.id,
+                &contact_account_id,
+                discount_account_id,
+                -discount,
+            );
+            actions.push(Action::AddTransaction {
+                transaction,
+                ledger_entries,
+            });
+        }
+        Ok(actions)
+    }
+
+    // late fee interest accrued at the balance date time, offset in the interest account
+    pub fn late_fee_actions(&self, interest_account_id: &AccountId) -> Result<Vec<Action>, Error> {
+        let contact_account_id = self
+            .contact_account_id
+            .ok_or(Error::InvalidInvoice(self.transaction_id))?;
+        if self.late_fee_due.is_zero() {
+            return Ok(Vec::new());
+        }
+        let transaction = Transaction::new(
+            self.date_time,
+            "Late fee interest".to_string(),
+            TransactionType::LateFee {
+                transaction_id: self.transaction_id,
+            },
+        );
+        let ledger_entries = self.entries(
+            &transaction.id,
+            &contact_account_id,
+            interest_account_id,
+            self.late_fee_due,
+        );
+        Ok(vec![Action::AddTransaction {
+            transaction,
+            ledger_entries,
+        }])
+    }
+
+    // entries changing the balance owed by amount, offset in the counter account
+    fn entries(
+        &self,
+        transaction_id: &TransactionId,
+        contact_account_id: &AccountId,
+        counter_account_id: &AccountId,
+        amount: Decimal,
+    ) -> Vec<LedgerEntry> {
+        let contact_entry_type = match (self.balance_type, amount.is_sign_positive()) {
+            (BalanceType::Receivable, true) | (BalanceType::Payable, false) => EntryType::Debit,
+            _ => EntryType::Credit,
+        };
+        let counter_entry_type = match contact_entry_type {
+            EntryType::Debit => EntryType::Credit,
+            EntryType::Credit => EntryType::Debit,
+        };
+        let currency_amount = CurrencyAmount::new(&self.currency_id, amount.abs());
+        vec![
+            LedgerEntry::new(
+                transaction_id,
+                contact_entry_type,
+                contact_account_id,
+                currency_amount.clone(),
+                None,
+            ),
+            LedgerEntry::new(
+                transaction_id,
+                counter_entry_type,
+                counter_account_id,
+                currency_amount,
+                None,
+            ),
+        ]
+    }
+
+    // issued invoices and bills with an outstanding balance at date time
+    pub fn outstanding(ledger: &Ledger, date_time: OffsetDateTime) -> Vec<InvoiceBalance> {
+        ledger
+            .transactions()
+            .iter()
+            .filter(|transaction| {
+                matches!(
+                    transaction.transaction_type,
+                    TransactionType::Invoice { .. } | TransactionType::Bill { .. }
+                )
+            })
+            .filter_map(|transaction| InvoiceBalance::new(ledger, &transaction.id, date_time).ok())
+            .filter(|balance| {
+                balance.status != InvoiceStatus::Draft && balance.outstanding > Decimal::ZERO
+            })
+            .collect()
+    }
+}
+
+/// Outstanding bill with the amount to pay in a period, from its payment schedules and any
+/// overdue scheduled payments if it has any or else the outstanding amount if it is due by the
+/// period end
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct BillDue {
+    pub balance: InvoiceBalance,
+    pub payment_schedules: Vec<Arc<PaymentSchedule>>,
+    pub amount_due: CurrencyAmount,
+}
+
+/// Bills to pay from start date time to date time with totals by currency, ie. to plan BTC and
+/// USD outflows
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct BillsDue {
+    pub start_date_time: OffsetDateTime,
+    pub date_time: OffsetDateTime,
+    pub bills: Vec<BillDue>,
+    pub totals: Vec<CurrencyAmount>,
+}
+
+impl BillsDue {
+    pub fn new(
+        ledger: &Ledger,
+        start_date_time: OffsetDateTime,
+        date_time: OffsetDateTime,
+    ) -> Self {
+        let mut bills = Vec::new();
+        let mut totals: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+        for balance in InvoiceBalance::outstanding(ledger, date_time) {
+            if balance.balance_type != BalanceType::Payable {
+                continue;
+            }
+            let all_schedules = ledger.get_payment_schedules(&balance.transaction_id);
+            let payment_schedules: Vec<Arc<PaymentSchedule>> = all_schedules
+                .iter()
+                .filter(|schedule| {
+                    start_date_time <= schedule.datetime && schedule.datetime <= date_time
+                })
+                .cloned()
+                .collect();
+            let amount = if !all_schedules.is_empty() {
+                let scheduled = |before: OffsetDateTime| -> Decimal {
+                    all_schedules
+                        .iter()
+                        .filter(|schedule| {
+                            schedule.datetime < before
+                                && schedule.currency_amount.currency_id == balance.currency_id
+                        })
+                        .map(|schedule| schedule.currency_amount.amount)
+                        .sum()
+                };
+                // payments scheduled before the period and not yet settled are overdue
+                let settled = balance.paid + balance.discounts + balance.written_off;
+                let overdue = (scheduled(start_date_time) - settled).max(Decimal::ZERO);
+                let due: Decimal = payment_schedules
+                    .iter()
+                    .filter(|schedule| schedule.currency_amount.currency_id == balance.currency_id)
+                    .map(|schedule| schedule.currency_amount.amount)
+                    .sum();
+                (overdue + due).min(balance.outstanding)
+            } else if balance.due_date_time <= date_time {
+                balance.outstanding
+            } else {
+                Decimal::ZERO
+            };
+            if amount.is_zero() {
+                continue;
+            }
+            *totals.entry(balance.currency_id).or_default() += amount;
+            bills.push(BillDue {
+                amount_due: CurrencyAmount::new(&balance.currency_id, amount),
+                balance,
+                payment_schedules,
+            });
+        }
+        bills.sort_by_key(|a| a.balance.due_date_time);
+        let totals = totals
+            .iter()
+            .map(|(currency_id, amount)| CurrencyAmount::new(currency_id, *amount))
+            .collect();
+
+        BillsDue {
+            start_date_time,
+            date_time,
+            bills,
+            totals,
+        }
+    }
+}
+
+/// Tax of a tax code on the subtotal of the line items it applies to
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TaxAmount {
+    pub tax_code: String,
+    pub account_id: AccountId,
+    pub taxable: Decimal,
+    pub amount: Decimal,
+}
+
+/// Line amounts, subtotal, taxes and total of an invoice or bill's line items, rounded to the
+/// currency's scale per line and per tax code
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct InvoiceTotals {
+    pub transaction_id: TransactionId,
+    pub balance_type: BalanceType,
+    pub currency_id: CurrencyId,
+    pub line_amounts: Vec<Decimal>,
+    pub subtotal: Decimal,
+    pub taxes: Vec<TaxAmount>,
+    pub total: Decimal,
+}
+
+impl InvoiceTotals {
+    // line items must have one currency, a positive quantity and a non-negative unit price
+    pub fn new(ledger: &Ledger, transaction: &Transaction) -> Result<Self, Error> {
+        let invalid = || Error::InvalidLineItems(transaction.id);
+        let (balance_type, line_items) =
+            line_items(transaction).ok_or(Error::InvalidInvoice(transaction.id))?;
+        let currency_id = line_items
+            .first()
+            .ok_or_else(invalid)?
+            .unit_price
+            .currency_id;
+        let currency = ledger
+            .get_currency(&currency_id)
+            .ok_or(Error::MissingCurrency(currency_id))?;
+        let rounded = |amount| Money::rounded(&currency, amount, RoundingMode::HalfUp).amount;
+
+        let mut line_amounts = Vec::new();
+        let mut taxable_amounts: BTreeMap<String, Decimal> = BTreeMap::new();
+        for line_item in line_items {
+            ledger.account_exists(&line_item.account_id)?;
+            if line_item.unit_price.currency_id != currency_id
+                || line_item.quantity <= Decimal::ZERO
+                || line_item.unit_price.amount < Decimal::ZERO
+            {
+                return Err(invalid());
+            }
+            let amount = rounded(line_item.quantity * line_item.unit_price.amount);
+            if let Some(tax_code) = &line_item.tax_code {
+                *taxable_amounts.entry(tax_code.clone()).or_default() += amount;
+            }
+            line_amounts.push(amount);
+        }
+        let mut taxes = Vec::new();
+        for (code, taxable) in taxable_amounts {
+            let tax_code = ledger
+                .get_tax_code(&code)
+                .ok_or(Error::MissingTaxCode(code))?;
+            taxes.push(TaxAmount {
+                tax_code: tax_code.code.clone(),
+                account_id: tax_code.account_id,
+                taxable,
+                amount: rounded(taxable * tax_code.rate),
+            });
+        }
+        let subtotal: Decimal = line_amounts.iter().sum();
+        let total = subtotal + taxes.iter().map(|tax| tax.amount).sum::<Decimal>();
+
+        Ok(InvoiceTotals {
+            transaction_id: transaction.id,
+            balance_type,
+            currency_id,
+            line_amounts,
+            subtotal,
+            taxes,
+            total,
+        })
+    }
+
+    // total to the contact account offset by the line item and tax accounts, invoices debit the
+    // contact's receivable account and bills credit the contact's payable account
+    pub fn ledger_entries(
+        &self,
+        transaction: &Transaction,
+        contact_account_id: &AccountId,
+    ) -> Vec<LedgerEntry> {
+        let entry_type = match self.balance_type {
+            BalanceType::Receivable => EntryType::Debit,
+            BalanceType::Payable => EntryType::Credit,
+        };
+        let mut ledger_entries = vec![LedgerEntry::new(
+            &self.transaction_id,
+            entry_type,
+            contact_account_id,
+            CurrencyAmount::new(&self.currency_id, self.total),
+            Some(transaction.description.clone()),
+        )];
+        ledger_entries.extend(self.line_entries(transaction));
+        ledger_entries
+    }
+
+    // line item and tax entries, without the contact account entry
+    fn line_entries(&self, transaction: &Transaction) -> Vec<LedgerEntry> {
+        let entry_type = match self.balance_type {
+            BalanceType::Receivable => EntryType::Credit,
+            BalanceType::Payable => EntryType::Debit,
+        };
+        let entry = |account_id: &AccountId, amount, description: &str| {
+            LedgerEntry::new(
+                &self.transaction_id,
+                entry_type.clone(),
+                account_id,
+                CurrencyAmount::new(&self.currency_id, amount),
+                Some(description.to_string()),
+            )
+        };
+        let line_items = line_items(transaction)
+            .map(|(_, line_items)| line_items.as_slice())
+            .unwrap_or_default();
+        let lines = line_items
+            .iter()
+            .zip(self.line_amounts.iter())
+            .map(|(line_item, amount)| {
+                entry(&line_item.account_id, *amount, &line_item.description)
+            });
+        let taxes = self
+            .taxes
+            .iter()
+            .map(|tax| entry(&tax.account_id, tax.amount, &tax.tax_code));
+        lines
+            .chain(taxes)
+            .filter(|entry| !entry.currency_amount.amount.is_zero())
+            .collect()
+    }
+}
+
+// ledger entries of an invoice or bill with line items must post its line amounts and taxes, and
+// its total to contact accounts
+pub fn line_items_valid(
+    ledger: &Ledger,
+    transaction: &Transaction,
+    ledger_entries: &[LedgerEntry],
+) -> Result<(), Error> {
+    match line_items(transaction) {
+        Some((_, line_items)) if !line_items.is_empty() => (),
+        _ => return Ok(()),
+    }
+    let totals = InvoiceTotals::new(ledger, transaction)?;
+    let (entry_type, category) = match totals.balance_type {
+        BalanceType::Receivable => (EntryType::Debit, AccountCategory::BalanceSheet(Asset)),
+        BalanceType::Payable => (EntryType::Credit, AccountCategory::BalanceSheet(Liability)),
+    };
+    let contact_entry = |entry: &LedgerEntry| {
+        entry.entry_type == entry_type
+            && ledger
+                .get_account(&entry.account_id)
+                .is_some_and(|account| {
+                    matches!(account.account_type, AccountType::ContactAccount { .. })
+                        && account.account_category == category
+                })
+    };
+    let (contact_entries, posted_entries): (Vec<&LedgerEntry>, Vec<&LedgerEntry>) = ledger_entries
+        .iter()
+        .partition(|entry| contact_entry(entry));
+    let contact_total = contact_entries
+        .iter()
+        .map(|entry| &entry.currency_amount)
+        .try_fold(Decimal::ZERO, |total, currency_amount| {
+            (currency_amount.currency_id == totals.currency_id)
+                .then(|| total + currency_amount.amount)
+        });
+    let expected_entries = totals.line_entries(transaction);
+    if contact_total != Some(totals.total)
+        || account_amounts(posted_entries) != account_amounts(expected_entries.iter())
+    {
+        return Err(Error::InvalidLineItems(transaction.id));
+    }
+    Ok(())
+}
+
+// invoice or bill balance type and line items
+fn line_items(transaction: &Transaction) -> Option<(BalanceType, &Vec<LineItem>)> {
+    match &transaction.transaction_type {
+        TransactionType::Invoice { line_items, .. } => Some((BalanceType::Receivable, line_items)),
+        TransactionType::Bill { line_items, .. } => Some((BalanceType::Payable, line_items)),
+        _ => None,
+    }
+}
+
+// non-zero debit positive amounts by account and currency
+fn account_amounts<'a>(
+    ledger_entries: impl IntoIterator<Item = &'a LedgerEntry>,
+) -> BTreeMap<(AccountId, CurrencyId), Decimal> {
+    let mut amounts: BTreeMap<(AccountId, CurrencyId), Decimal> = BTreeMap::new();
+    for entry in ledger_entries {
+        let amount = match entry.entry_type {
+            EntryType::Debit => entry.currency_amount.amount,
+            EntryType::Credit => -entry.currency_amount.amount,
+        };
+        *amounts
+            .entry((entry.account_id, entry.currency_amount.currency_id))
+            .or_default() += amount;
+    }
+    amounts.retain(|_, amount| !amount.is_zero());
+    amounts
+}
+
+// scheduled accrual of late fee interest on overdue receivable or payable invoices
+pub fn late_fee_accrual_actions(
+    ledger: &Ledger,
+    balance_type: BalanceType,
+    interest_account_id: &AccountId,
+    date_time: OffsetDateTime,
+) -> Result<Vec<Action>, Error> {
+    ledger.account_exists(interest_account_id)?;
+    let mut actions = Vec::new();
+    for balance in InvoiceBalance::outstanding(ledger, date_time) {
+        if balance.balance_type == balance_type {
+            actions.append(&mut balance.late_fee_actions(interest_account_id)?);
+        }
+    }
+    Ok(actions)
+}
+
+// contact asset accounts debited by an invoice or contact liability accounts credited by a bill
+pub fn contact_account_ids(
+    ledger: &Ledger,
+    transaction_id: &TransactionId,
+) -> (BalanceType, Vec<AccountId>) {
+    let (balance_type, entry_type, category) = match ledger
+        .get_transaction(transaction_id)
+        .map(|transaction| transaction.transaction_type.clone())
+    {
+        Some(TransactionType::Bill { .. }) => (
+            BalanceType::Payable,
+            EntryType::Credit,
+            AccountCategory::BalanceSheet(Liability),
+        ),
+        _ => (
+            BalanceType::Receivable,
+            EntryType::Debit,
+            AccountCategory::BalanceSheet(Asset),
+        ),
+    };
+    let mut account_ids: Vec<AccountId> = ledger
+        .get_transaction_entries(transaction_id)
+        .iter()
+        .flatten()
+        .filter(|entry| entry.entry_type == entry_type)
+        .filter_map(|entry| ledger.get_account(&entry.account_id))
+        .filter(|account| {
+            matches!(account.account_type, AccountType::ContactAccount { .. })
+                && account.account_category == category
+        })
+        .map(|account| account.id)
+        .collect();
+    account_ids.sort();
+    account_ids.dedup();
+    (balance_type, account_ids)
+}
+
+// transaction posted by date time and not voided or reversed
+fn posted(ledger: &Ledger, transaction: &Transaction, date_time: &OffsetDateTime) -> bool {
+    transaction.datetime <= *date_time
+        && ledger.get_void(&transaction.id).is_none()
+        && ledger
+            .get_reversal(&transaction.id)
+            .is_none_or(|reversal| reversal.datetime > *date_time)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::journal::Action::{
+        AddAccount, AddPayment, AddTaxCode, AddTransaction, SchedulePayment,
+    };
+    use crate::journal::{
+        test_entries, Account, AccountCategory, AccountId, AccountType, BalanceSheetCategory,
+        CurrencyAmount, EntryType, JournalEntry, LedgerEntry, LineItem, Payment, PaymentMethod,
+        PaymentSchedule, PaymentTerms, TaxCode, Transaction, TransactionType,
+    };
+    use crate::ledger::invoice::{
+        late_fee_accrual_actions, BalanceType, BillsDue, InvoiceBalance, InvoiceStatus,
+        InvoiceTotals,
+    };
+    use crate::ledger::test::setup;
+    use crate::ledger::{Error, OrganizationLedgers};
+    use crate::lightning::test::TEST_INVOICE;
+    use crate::lightning::Bolt11Invoice;
+    use rust_decimal::Decimal;
+    use rusty_ulid::Ulid;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn test_invoice_balance() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let bank_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let contact = test_entries.contacts.get(0).expect("owner");
+        let receivable_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Owner Receivable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: contact.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: receivable_account.clone(),
+                },
+            ))
+            .expect("add account");
+
+        let entry = |transaction_id: &Ulid, entry_type: EntryType, account_id: &Ulid, cents| {
+            LedgerEntry::new(
+                transaction_id,
+                entry_type,
+                account_id,
+                CurrencyAmount::new(&usd_id, Decimal::new(cents, 2)),
+                None,
+            )
+        };
+        let invoice = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Consulting services".to_string(),
+            TransactionType::Invoice {
+                payment_method: PaymentMethod::Cash,
+                payment_terms: PaymentTerms::NetDays {
+                    days: 30,
+                    late_fee_interest: Decimal::ZERO,
+                },
+                payments: vec![],
+                line_items: vec![],
+            },
+        );
+        let ledger_entries = vec![
+            entry(
+                &invoice.id,
+                EntryType::Debit,
+                &receivable_account.id,
+                1_000_00,
+            ),
+            entry(&invoice.id, EntryType::Credit, &income_account_id, 1_000_00),
+        ];
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: invoice.clone(),
+                    ledger_entries,
+                },
+            ))
+            .expect("add invoice");
+
+        let mut add_payment = |datetime, cents, payment_cents| {
+            let transaction = Transaction::new(
+                datetime,
+                "Customer payment".to_string(),
+                TransactionType::InvoicePayment {
+                    transaction_id: invoice.id,
+                },
+            );
+            let ledger_entries = vec![
+                entry(&transaction.id, EntryType::Debit, &bank_account_id, cents),
+                entry(
+                    &transaction.id,
+                    EntryType::Credit,
+                    &receivable_account.id,
+                    cents,
+                ),
+            ];
+            let payment = Payment::Cash {
+                date: date!(2022 - 03 - 15),
+                currency_id: usd_id,
+                amount: Decimal::new(payment_cents, 2),
+            };
+            organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddPayment {
+                    transaction_id: invoice.id,
+                    payment,
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+        };
+        let result = add_payment(datetime!(2022-03-15 09:00 UTC), 400_00, 500_00);
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+        add_payment(datetime!(2022-03-15 09:00 UTC), 400_00, 400_00).expect("add payment");
+        let result = add_payment(datetime!(2022-03-16 09:00 UTC), 700_00, 700_00);
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+
+        // payments must be added with their payment record
+        let transaction = Transaction::new(
+            datetime!(2022-03-16 09:00 UTC),
+            "Customer payment".to_string(),
+            TransactionType::InvoicePayment {
+                transaction_id: invoice.id,
+            },
+        );
+        let ledger_entries = vec![
+            entry(&transaction.id, EntryType::Debit, &bank_account_id, 100_00),
+            entry(
+                &transaction.id,
+                EntryType::Credit,
+                &receivable_account.id,
+                100_00,
+            ),
+        ];
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            AddTransaction {
+                transaction,
+                ledger_entries,
+            },
+        ));
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance = |date_time| InvoiceBalance::new(ledger, &invoice.id, date_time);
+        let draft = balance(datetime!(2022-02-28 09:00 UTC)).expect("draft");
+        assert_eq!(draft.status, InvoiceStatus::Draft);
+        let issued = balance(datetime!(2022-03-10 09:00 UTC)).expect("issued");
+        assert_eq!(issued.status, InvoiceStatus::Issued);
+        assert_eq!(issued.due_date_time, datetime!(2022-03-31 09:00 UTC));
+        assert_eq!(issued.contact_id, Some(contact.id));
+        let partially_paid = balance(datetime!(2022-03-20 09:00 UTC)).expect("partially paid");
+        assert_eq!(partially_paid.status, InvoiceStatus::PartiallyPaid);
+        assert_eq!(partially_paid.outstanding, Decimal::new(600_00, 2));
+        let overdue = balance(datetime!(2022-04-01 09:00 UTC)).expect("overdue");
+        assert_eq!(overdue.status, InvoiceStatus::Overdue);
+        let invoice_transaction = ledger.get_transaction(&invoice.id).expect("invoice");
+        assert!(matches!(
+            &invoice_transaction.transaction_type,
+            TransactionType::Invoice { payments, .. } if payments.len() == 1
+        ));
+
+        // write off the remaining balance
+        let mut add_write_off = |account_id: &AccountId, amount| {
+            let write_off = Transaction::new(
+                datetime!(2022-04-15 09:00 UTC),
+                "Write off uncollectible balance".to_string(),
+                TransactionType::WriteOff {
+                    transaction_id: invoice.id,
+                },
+            );
+            let ledger_entries = vec![
+                entry(&write_off.id, EntryType::Debit, &income_account_id, amount),
+                entry(&write_off.id, EntryType::Credit, account_id, amount),
+            ];
+            organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: write_off,
+                    ledger_entries,
+                },
+            ))
+        };
+        let result = add_write_off(&bank_account_id, 600_00);
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+        let result = add_write_off(&receivable_account.id, 700_00);
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+        add_write_off(&receivable_account.id, 600_00).expect("add write off");
+        let result = add_write_off(&receivable_account.id, 600_00);
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let written_off = InvoiceBalance::new(ledger, &invoice.id, datetime!(2022-04-30 09:00 UTC))
+            .expect("written off");
+        assert_eq!(written_off.status, InvoiceStatus::WrittenOff);
+        assert_eq!(written_off.written_off, Decimal::new(600_00, 2));
+        assert_eq!(written_off.outstanding, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_discounts_and_late_fees() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let bank_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let discount_account_id = test_entries.accounts.get(7).expect("office supplies").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let contact = test_entries.contacts.get(0).expect("owner");
+        let receivable_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Owner Receivable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: contact.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: receivable_account.clone(),
+                },
+            ))
+            .expect("add account");
+
+        // 2% discount within 10 days, 18.25% annual late fee interest is 0.05% per day
+        let late_fee_interest = Decimal::new(1825, 4);
+        let terms = [
+            PaymentTerms::NetDaysDiscount {
+                days: 30,
+                discount_days: 10,
+                discount: Decimal::new(2, 2),
+                late_fee_interest,
+            },
+            PaymentTerms::NetDays {
+                days: 30,
+                late_fee_interest,
+            },
+        ];
+        let mut invoice_ids = Vec::new();
+        for payment_terms in terms {
+            let invoice = Transaction::new(
+                datetime!(2022-03-01 09:00 UTC),
+                "Consulting services".to_string(),
+                TransactionType::Invoice {
+                    payment_method: PaymentMethod::Cash,
+                    payment_terms,
+                    payments: vec![],
+                    line_items: vec![],
+                },
+            );
+            let amount = CurrencyAmount::new(&usd_id, Decimal::new(1_000_00, 2));
+            let ledger_entries = vec![
+                LedgerEntry::new(
+                    &invoice.id,
+                    EntryType::Debit,
+                    &receivable_account.id,
+                    amount.clone(),
+                    None,
+                ),
+                LedgerEntry::new(
+                    &invoice.id,
+                    EntryType::Credit,
+                    &income_account_id,
+                    amount,
+                    None,
+                ),
+            ];
+            invoice_ids.push(invoice.id);
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddTransaction {
+                        transaction: invoice,
+                        ledger_entries,
+                    },
+                ))
+                .expect("add invoice");
+        }
+        let discount_invoice_id = invoice_ids.get(0).expect("discount invoice");
+        let late_invoice_id = invoice_ids.get(1).expect("late invoice");
+
+        // early payment takes the discount
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance =
+            InvoiceBalance::new(ledger, discount_invoice_id, datetime!(2022-03-05 09:00 UTC))
+                .expect("balance");
+        assert_eq!(balance.discount_available, Decimal::new(20_00, 2));
+        let payment = Payment::Cash {
+            date: date!(2022 - 03 - 05),
+            currency_id: usd_id,
+            amount: Decimal::new(980_00, 2),
+        };
+        let actions = balance
+            .payment_actions(payment, &bank_account_id, &discount_account_id)
+            .expect("payment actions");
+        assert_eq!(actions.len(), 2);
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add payment");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance =
+            InvoiceBalance::new(ledger, discount_invoice_id, datetime!(2022-04-10 09:00 UTC))
+                .expect("balance");
+        assert_eq!(balance.status, InvoiceStatus::Paid);
+        assert_eq!(balance.discounts, Decimal::new(20_00, 2));
+        assert_eq!(balance.late_fee_due, Decimal::ZERO);
+
+        // scheduled accrual charges interest on overdue invoices only
+        let actions = late_fee_accrual_actions(
+            ledger,
+            BalanceType::Receivable,
+            &income_account_id,
+            datetime!(2022-04-10 09:00 UTC),
+        )
+        .expect("accrual actions");
+        assert_eq!(actions.len(), 1);
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add late fee");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance = InvoiceBalance::new(ledger, late_invoice_id, datetime!(2022-04-10 09:00 UTC))
+            .expect("balance");
+        assert_eq!(balance.late_fees, Decimal::new(5_00, 2));
+        assert_eq!(balance.outstanding, Decimal::new(1_005_00, 2));
+        assert_eq!(balance.late_fee_due, Decimal::ZERO);
+        let balance = InvoiceBalance::new(ledger, late_invoice_id, datetime!(2022-04-20 09:00 UTC))
+            .expect("balance");
+        assert_eq!(balance.late_fee_due, Decimal::new(5_00, 2));
+
+        // interest after a partial payment accrues on the amount unpaid in each interval
+        let balance = InvoiceBalance::new(ledger, late_invoice_id, datetime!(2022-04-15 09:00 UTC))
+            .expect("balance");
+        let payment = Payment::Cash {
+            date: date!(2022 - 04 - 15),
+            currency_id: usd_id,
+            amount: Decimal::new(500_00, 2),
+        };
+        let actions = balance
+            .payment_actions(payment, &bank_account_id, &discount_account_id)
+            .expect("payment actions");
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add payment");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance = InvoiceBalance::new(ledger, late_invoice_id, datetime!(2022-04-20 09:00 UTC))
+            .expect("balance");
+        assert_eq!(balance.paid, Decimal::new(500_00, 2));
+        assert_eq!(balance.late_fee_due, Decimal::new(3_75, 2));
+    }
+
+    #[test]
+    fn test_bills_due() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let liabilities = test_entries.accounts.get(1).expect("liabilities");
+        let bank_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let supplies_account_id = test_entries.accounts.get(7).expect("office supplies").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let vendor = test_entries.contacts.get(1).expect("bank");
+        let payable_account = Account::new(
+            Some(&liabilities.id),
+            300,
+            "Bank Payable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: vendor.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Liability),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: payable_account.clone(),
+                },
+            ))
+            .expect("add account");
+
+        let entry = |transaction_id: &Ulid, entry_type, account_id: &Ulid, amount| {
+            LedgerEntry::new(transaction_id, entry_type, account_id, amount, None)
+        };
+        let mut add_bill = |datetime,
+                            payment_method,
+                            payment_terms,
+                            credit_account_id: &Ulid,
+                            amount: CurrencyAmount| {
+            let bill = Transaction::new(
+                datetime,
+                "Office supplies".to_string(),
+                TransactionType::Bill {
+                    payment_method,
+                    payment_terms,
+                    payments: vec![],
+                    line_items: vec![],
+                },
+            );
+            let ledger_entries = vec![
+                entry(
+                    &bill.id,
+                    EntryType::Debit,
+                    &supplies_account_id,
+                    amount.clone(),
+                ),
+                entry(&bill.id, EntryType::Credit, credit_account_id, amount),
+            ];
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddTransaction {
+                        transaction: bill.clone(),
+                        ledger_entries,
+                    },
+                ))
+                .map(|_| bill.id)
+        };
+        let net_30 = PaymentTerms::NetDays {
+            days: 30,
+            late_fee_interest: Decimal::ZERO,
+        };
+        let usd_amount = |cents| CurrencyAmount::new(&usd_id, Decimal::new(cents, 2));
+
+        // bills must credit a vendor payable account
+        let result = add_bill(
+            datetime!(2022-03-01 09:00 UTC),
+            PaymentMethod::Cash,
+            net_30.clone(),
+            &income_account_id,
+            usd_amount(1_000_00),
+        );
+        assert!(matches!(result, Err(Error::InvalidInvoice(_))));
+        let usd_bill_id = add_bill(
+            datetime!(2022-03-01 09:00 UTC),
+            PaymentMethod::Cash,
+            net_30,
+            &payable_account.id,
+            usd_amount(1_000_00),
+        )
+        .expect("add usd bill");
+        // a bill's bitcoin address is the vendor's, not one of our receive addresses
+        let btc_bill_id = add_bill(
+            datetime!(2022-03-10 09:00 UTC),
+            PaymentMethod::Bitcoin {
+                address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+                account_id: Some(Ulid::generate()),
+                derivation_index: Some(0),
+            },
+            PaymentTerms::ImmediatePayment,
+            &payable_account.id,
+            CurrencyAmount::new(&btc_id, Decimal::new(1_000_000, 8)),
+        )
+        .expect("add btc bill");
+
+        // schedule the usd bill in two payments and pay the first
+        for (datetime, cents) in [
+            (datetime!(2022-03-20 09:00 UTC), 400_00),
+            (datetime!(2022-04-15 09:00 UTC), 600_00),
+        ] {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    SchedulePayment {
+                        payment_schedule: PaymentSchedule::new(
+                            &usd_bill_id,
+                            datetime,
+                            usd_amount(cents),
+                        ),
+                    },
+                ))
+                .expect("schedule payment");
+        }
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            SchedulePayment {
+                payment_schedule: PaymentSchedule::new(
+                    &Ulid::generate(),
+                    datetime!(2022-03-20 09:00 UTC),
+                    usd_amount(100_00),
+                ),
+            },
+        ));
+        assert!(matches!(result, Err(Error::MissingTransaction(_))));
+        let transaction = Transaction::new(
+            datetime!(2022-03-20 09:00 UTC),
+            "Vendor payment".to_string(),
+            TransactionType::InvoicePayment {
+                transaction_id: usd_bill_id,
+            },
+        );
+        let ledger_entries = vec![
+            entry(
+                &transaction.id,
+                EntryType::Debit,
+                &payable_account.id,
+                usd_amount(400_00),
+            ),
+            entry(
+                &transaction.id,
+                EntryType::Credit,
+                &bank_account_id,
+                usd_amount(400_00),
+            ),
+        ];
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddPayment {
+                    transaction_id: usd_bill_id,
+                    payment: Payment::Check {
+                        check_number: 1001,
+                        check_routing: 123456789,
+                        check_account: 1234,
+                        date: date!(2022 - 03 - 20),
+                        currency_id: usd_id,
+                        amount: Decimal::new(400_00, 2),
+                        memo: "Office supplies".to_string(),
+                    },
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+            .expect("add payment");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance = InvoiceBalance::new(ledger, &usd_bill_id, datetime!(2022-03-25 09:00 UTC))
+            .expect("balance");
+        assert_eq!(balance.balance_type, BalanceType::Payable);
+        assert_eq!(balance.status, InvoiceStatus::PartiallyPaid);
+        assert_eq!(balance.outstanding, Decimal::new(600_00, 2));
+
+        // only the btc bill is due when no usd payment is scheduled
+        let bills_due = BillsDue::new(
+            ledger,
+            datetime!(2022-03-21 00:00 UTC),
+            datetime!(2022-03-31 23:59 UTC),
+        );
+        assert_eq!(bills_due.bills.len(), 1);
+        assert_eq!(
+            bills_due
+                .bills
+                .get(0)
+                .expect("btc bill")
+                .balance
+                .transaction_id,
+            btc_bill_id
+        );
+        let bills_due = BillsDue::new(
+            ledger,
+            datetime!(2022-04-01 00:00 UTC),
+            datetime!(2022-04-30 23:59 UTC),
+        );
+        assert_eq!(bills_due.bills.len(), 2);
+        let usd_bill = bills_due
+            .bills
+            .iter()
+            .find(|bill| bill.balance.transaction_id == usd_bill_id)
+            .expect("usd bill");
+        assert_eq!(usd_bill.payment_schedules.len(), 1);
+        assert_eq!(
+            bills_due.totals,
+            vec![
+                usd_amount(600_00),
+                CurrencyAmount::new(&btc_id, Decimal::new(1_000_000, 8)),
+            ]
+        );
+
+        // the missed april payment is still due in may
+        let bills_due = BillsDue::new(
+            ledger,
+            datetime!(2022-05-01 00:00 UTC),
+            datetime!(2022-05-31 23:59 UTC),
+        );
+        let usd_bill = bills_due
+            .bills
+            .iter()
+            .find(|bill| bill.balance.transaction_id == usd_bill_id)
+            .expect("usd bill");
+        assert!(usd_bill.payment_schedules.is_empty());
+        assert_eq!(usd_bill.amount_due, usd_amount(600_00));
+    }
+
+    #[test]
+    fn test_line_items() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let liabilities = test_entries.accounts.get(1).expect("liabilities");
+        let supplies_account_id = test_entries.accounts.get(7).expect("office supplies").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let customer = test_entries.contacts.get(0).expect("owner");
+        let vendor = test_entries.contacts.get(1).expect("bank");
+        let receivable_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Owner Receivable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: customer.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        let payable_account = Account::new(
+            Some(&liabilities.id),
+            300,
+            "Bank Payable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: vendor.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Liability),
+        );
+        let tax_account = Account::new(
+            Some(&liabilities.id),
+            310,
+            "Sales Tax Payable".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Liability),
+        );
+        for account in [
+            receivable_account.clone(),
+            payable_account.clone(),
+            tax_account.clone(),
+        ] {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddAccount { account },
+                ))
+                .expect("add account");
+        }
+        let tax_code = TaxCode::new(
+            "ST".to_string(),
+            "Sales tax".to_string(),
+            Decimal::new(825, 4),
+            &tax_account.id,
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTaxCode {
+                    tax_code: tax_code.clone(),
+                },
+            ))
+            .expect("add tax code");
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            AddTaxCode { tax_code },
+        ));
+        assert!(matches!(result, Err(Error::TaxCodeExists(_))));
+
+        let usd_amount = |cents| CurrencyAmount::new(&usd_id, Decimal::new(cents, 2));
+        let line_items = vec![
+            LineItem::new(
+                "Consulting hours".to_string(),
+                Decimal::new(125, 1),
+                usd_amount(150_00),
+                &income_account_id,
+                None,
+            ),
+            LineItem::new(
+                "Software licenses".to_string(),
+                Decimal::new(3, 0),
+                usd_amount(33_33),
+                &income_account_id,
+                Some("ST".to_string()),
+            ),
+        ];
+        let invoice_type = |line_items| TransactionType::Invoice {
+            payment_method: PaymentMethod::Cash,
+            payment_terms: PaymentTerms::ImmediatePayment,
+            payments: vec![],
+            line_items,
+        };
+        let invoice = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Consulting services".to_string(),
+            invoice_type(line_items.clone()),
+        );
+
+        // generated entries match the line items, subtotal and tax
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let totals = InvoiceTotals::new(ledger, &invoice).expect("totals");
+        assert_eq!(
+            totals.line_amounts,
+            vec![Decimal::new(1_875_00, 2), Decimal::new(99_99, 2)]
+        );
+        assert_eq!(totals.subtotal, Decimal::new(1_974_99, 2));
+        assert_eq!(totals.taxes.len(), 1);
+        assert_eq!(
+            totals.taxes.get(0).expect("sales tax").amount,
+            Decimal::new(8_25, 2)
+        );
+        assert_eq!(totals.total, Decimal::new(1_983_24, 2));
+        let ledger_entries = totals.ledger_entries(&invoice, &receivable_account.id);
+        assert_eq!(ledger_entries.len(), 4);
+
+        // entries that leave out the tax do not match the line items
+        let mut untaxed_entries: Vec<LedgerEntry> = ledger_entries
+            .iter()
+            .filter(|entry| entry.account_id != tax_account.id)
+            .cloned()
+            .collect();
+        untaxed_entries
+            .get_mut(0)
+            .expect("receivable")
+            .currency_amount = usd_amount(1_974_99);
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            AddTransaction {
+                transaction: invoice.clone(),
+                ledger_entries: untaxed_entries,
+            },
+        ));
+        assert!(matches!(result, Err(Error::InvalidLineItems(_))));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: invoice.clone(),
+                    ledger_entries,
+                },
+            ))
+            .expect("add invoice");
+
+        // line items with an unknown tax code are not valid
+        let mut line_items = line_items;
+        line_items.get_mut(1).expect("licenses").tax_code = Some("VAT".to_string());
+        let unknown_tax = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Consulting services".to_string(),
+            invoice_type(line_items),
+        );
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let result = InvoiceTotals::new(ledger, &unknown_tax);
+        assert!(matches!(result, Err(Error::MissingTaxCode(_))));
+
+        // bills credit the payable account and debit the line item and tax accounts
+        let bill = Transaction::new(
+            datetime!(2022-03-05 09:00 UTC),
+            "Office supplies".to_string(),
+            TransactionType::Bill {
+                payment_method: PaymentMethod::Cash,
+                payment_terms: PaymentTerms::ImmediatePayment,
+                payments: vec![],
+                line_items: vec![LineItem::new(
+                    "Printer paper".to_string(),
+                    Decimal::new(2, 0),
+                    usd_amount(10_00),
+                    &supplies_account_id,
+                    Some("ST".to_string()),
+                )],
+            },
+        );
+        let totals = InvoiceTotals::new(ledger, &bill).expect("totals");
+        assert_eq!(totals.total, Decimal::new(21_65, 2));
+        let ledger_entries = totals.ledger_entries(&bill, &payable_account.id);
+        let payable_entry = ledger_entries.get(0).expect("payable");
+        assert_eq!(payable_entry.entry_type, EntryType::Credit);
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: bill.clone(),
+                    ledger_entries,
+                },
+            ))
+            .expect("add bill");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let invoice_balance =
+            InvoiceBalance::new(ledger, &invoice.id, datetime!(2022-03-10 09:00 UTC))
+                .expect("invoice balance");
+        assert_eq!(invoice_balance.outstanding, Decimal::new(1_983_24, 2));
+        let bill_balance = InvoiceBalance::new(ledger, &bill.id, datetime!(2022-03-10 09:00 UTC))
+            .expect("bill balance");
+        assert_eq!(bill_balance.balance_type, BalanceType::Payable);
+        assert_eq!(bill_balance.outstanding, Decimal::new(21_65, 2));
+    }
+
+    #[test]
+    fn test_lightning_invoice_payment() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let bank_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let contact = test_entries.contacts.get(0).expect("owner");
+        let receivable_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Owner Receivable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: contact.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: receivable_account.clone(),
+                },
+            ))
+            .expect("add account");
+
+        // 1,500 msat lightning invoice, not a whole number of satoshis
+        let (_, data, variant) = bech32::decode(TEST_INVOICE).expect("decode");
+        let bolt11 = bech32::encode("lnbc15n", data, variant).expect("encode");
+        let bolt11_invoice = Bolt11Invoice::parse(&bolt11).expect("bolt11 invoice");
+        assert_eq!(bolt11_invoice.amount_msat, Some(1_500));
+        let amount = CurrencyAmount::new(&btc_id, Decimal::new(1_500, 11));
+        let entry = |transaction_id: &Ulid, entry_type: EntryType, account_id: &Ulid| {
+            LedgerEntry::new(transaction_id, entry_type, account_id, amount.clone(), None)
+        };
+        let invoice = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Consulting services".to_string(),
+            TransactionType::Invoice {
+                payment_method: PaymentMethod::Lightning {
+                    invoice: bolt11.clone(),
+                },
+                payment_terms: PaymentTerms::ImmediatePayment,
+                payments: vec![],
+                line_items: vec![],
+            },
+        );
+        let ledger_entries = vec![
+            entry(&invoice.id, EntryType::Debit, &receivable_account.id),
+            entry(&invoice.id, EntryType::Credit, &income_account_id),
+        ];
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: invoice.clone(),
+                    ledger_entries,
+                },
+            ))
+            .expect("add invoice");
+
+        // settled in exact millisatoshis
+        let settled = datetime!(2022-03-01 09:05 UTC);
+        let payment = bolt11_invoice
+            .payment(None, None, Some(settled))
+            .expect("payment");
+        let transaction = Transaction::new(
+            settled,
+            "Lightning payment".to_string(),
+            TransactionType::InvoicePayment {
+                transaction_id: invoice.id,
+            },
+        );
+        let ledger_entries = vec![
+            entry(&transaction.id, EntryType::Debit, &bank_account_id),
+            entry(&transaction.id, EntryType::Credit, &receivable_account.id),
+        ];
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddPayment {
+                    transaction_id: invoice.id,
+                    payment,
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+            .expect("add payment");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let balance = InvoiceBalance::new(ledger, &invoice.id, datetime!(2022-03-01 10:00 UTC))
+            .expect("balance");
+        assert_eq!(balance.paid, Decimal::new(1_500, 11));
+        assert_eq!(balance.outstanding, Decimal::ZERO);
+        assert_eq!(balance.status, InvoiceStatus::Paid);
+    }
+
+    #[test]
+    fn test_immediate_payment_status() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let bank_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let contact = test_entries.contacts.get(0).expect("owner");
+        let receivable_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Owner Receivable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: contact.id,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: receivable_account.clone(),
+                },
+            ))
+            .expect("add account");
+
+        let entry = |transaction_id: &Ulid, entry_type: EntryType, account_id: &Ulid, cents| {
+            LedgerEntry::new(
+                transaction_id,
+                entry_type,
+                account_id,
+                CurrencyAmount::new(&usd_id, Decimal::new(cents, 2)),
+                None,
+            )
+        };
+        let invoice = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Consulting services".to_string(),
+            TransactionType::Invoice {
+                payment_method: PaymentMethod::Cash,
+                payment_terms: PaymentTerms::ImmediatePayment,
+                payments: vec![],
+                line_items: vec![],
+            },
+        );
+        let ledger_entries = vec![
+            entry(
+                &invoice.id,
+                EntryType::Debit,
+                &receivable_account.id,
+                500_00,
+            ),
+            entry(&invoice.id, EntryType::Credit, &income_account_id, 500_00),
+        ];
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: invoice.clone(),
+                    ledger_entries,
+                },
+            ))
+            .expect("add invoice");
+        let transaction = Transaction::new(
+            datetime!(2022-03-01 12:00 UTC),
+            "Customer payment".to_string(),
+            TransactionType::InvoicePayment {
+                transaction_id: invoice.id,
+            },
+        );
+        let ledger_entries = vec![
+            entry(&transaction.id, EntryType::Debit, &bank_account_id, 200_00),
+            entry(
+                &transaction.id,
+                EntryType::Credit,
+                &receivable_account.id,
+                200_00,
+            ),
+        ];
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddPayment {
+                    transaction_id: invoice.id,
+                    payment: Payment::Cash {
+                        date: date!(2022 - 03 - 01),
+                        currency_id: usd_id,
+                        amount: Decimal::new(200_00, 2),
+                    },
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+            .expect("add payment");
+
+        // due on the invoice date and overdue the day after
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let status = |date_time| {
+            InvoiceBalance::new(ledger, &invoice.id, date_time)
+                .expect("balance")
+                .status
+        };
+        assert_eq!(
+            status(datetime!(2022-03-01 10:00 UTC)),
+            InvoiceStatus::Issued
+        );
+        assert_eq!(
+            status(datetime!(2022-03-01 23:59 UTC)),
+            InvoiceStatus::PartiallyPaid
+        );
+        assert_eq!(
+            status(datetime!(2022-03-02 00:00 UTC)),
+            InvoiceStatus::Overdue
+        );
+    }
+}
@@ -1,23 +1,41 @@
+use crate::journal::money::Money;
 use crate::journal::Action::{
-    AddAccount, AddContact, AddCurrency, AddOrganization, AddTransaction,
+    AddAccount, AddContact, AddCurrency, AddExchangeRate, AddOrganization, AddPayment, AddTaxCode,
+    AddTransaction, ArchiveAccount, ClosePeriod, Reconcile, ReverseTransaction, SchedulePayment,
+    UpdateAccount, UpdateContact, VoidTransaction,
 };
 use crate::journal::{
-    Account, AccountCategory, AccountId, AccountNumber, AccountType, Contact, ContactId, Currency,
-    CurrencyId, JournalEntry, LedgerEntry, Organization, OrganizationId, Transaction,
-    TransactionId,
+    btc_currency_id, Account, AccountCategory, AccountId, AccountNumber, AccountType,
+    BalanceSheetCategory, Contact, ContactId, Currency, CurrencyAmount, CurrencyId, CurrencyScale,
+    EntryType, ExchangeRate, JournalEntry, LedgerEntry, Organization, OrganizationId, Payment,
+    PaymentMethod, PaymentSchedule, PeriodClose, Reconciliation, TaxCode, Transaction,
+    TransactionId, TransactionType, TransactionVoid, UnitAmount, MAX_CURRENCY_SCALE,
 };
+use crate::lightning;
+use crate::lightning::Bolt11Invoice;
 
 use log::error;
+use rust_decimal::Decimal;
+use std::borrow::Borrow;
 use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
 
+pub mod cost_basis;
+pub mod invoice;
 pub mod report;
+pub mod revaluation;
 
 #[derive(Debug, Clone)]
 pub enum Error {
     MissingAccount(AccountId),
     AccountExists(AccountId),
+    AccountCategoryMismatch(AccountId),
+    AccountNumberExists(AccountNumber),
+    AccountParentCycle(AccountId),
+    AccountTypeMismatch(AccountId),
+    AccountArchived(AccountId),
     MissingCurrency(CurrencyId),
     CurrencyExists(CurrencyId),
     MissingContact(ContactId),
@@ -25,6 +43,33 @@ pub enum Error {
     MissingTransaction(TransactionId),
     TransactionExists(TransactionId),
     LedgerEntriesExists(TransactionId),
+    MissingLedgerEntries(TransactionId),
+    TransactionIdMismatch(TransactionId),
+    UnbalancedLedgerEntries(TransactionId, CurrencyId),
+    InvalidEntryAmount(TransactionId),
+    CurrencyScaleExceeded(CurrencyId),
+    TransactionReversed(TransactionId),
+    TransactionVoided(TransactionId),
+    InvalidReversal(TransactionId),
+    PeriodLocked(TransactionId),
+    PeriodOutOfOrder(TransactionId),
+    PeriodGap(TransactionId),
+    TxidExists(String),
+    TransactionReconciled(TransactionId),
+    UnbalancedReconciliation(AccountId),
+    InvalidPayment(TransactionId),
+    AddressIndexUsed(AccountId, u32),
+    InvalidAddressIndex(AccountId, u32),
+    InvalidExchangeRate(CurrencyId, CurrencyId),
+    ExchangeRateExists(CurrencyId, CurrencyId),
+    MissingExchangeRate(CurrencyId, CurrencyId),
+    MissingCurrencyUnit(CurrencyId, String),
+    InvalidCurrencyUnit(CurrencyId, String),
+    InvalidInvoice(TransactionId),
+    MissingTaxCode(String),
+    TaxCodeExists(String),
+    InvalidTaxCode(String),
+    InvalidLineItems(TransactionId),
     MissingOrganization(OrganizationId),
     OrganizationExists(OrganizationId),
 }
@@ -34,6 +79,11 @@ impl Display for Error {
         match self {
             Self::MissingAccount(a) => write!(f, "missing account: {}", a),
             Self::AccountExists(a) => write!(f, "account exists: {}", a),
+            Self::AccountCategoryMismatch(a) => write!(f, "account category mismatch: {}", a),
+            Self::AccountNumberExists(n) => write!(f, "account number exists: {}", n),
+            Self::AccountParentCycle(a) => write!(f, "account parent cycle: {}", a),
+            Self::AccountTypeMismatch(a) => write!(f, "account type mismatch: {}", a),
+            Self::AccountArchived(a) => write!(f, "account archived: {}", a),
             Self::MissingCurrency(c) => write!(f, "missing currency: {}", c),
             Self::CurrencyExists(c) => write!(f, "currency exists: {}", c),
             Self::MissingContact(c) => write!(f, "missing contact: {}", c),
@@ -41,6 +91,35 @@ impl Display for Error {
             Self::MissingTransaction(t) => write!(f, "missing transaction: {}", t),
             Self::TransactionExists(t) => write!(f, "transaction exists: {}", t),
             Self::LedgerEntriesExists(t) => write!(f, "transaction entries exists: {}", t),
+            Self::MissingLedgerEntries(t) => write!(f, "missing transaction entries: {}", t),
+            Self::TransactionIdMismatch(t) => write!(f, "transaction id mismatch: {}", t),
+            Self::UnbalancedLedgerEntries(t, c) => {
+                write!(f, "unbalanced transaction entries: {}, currency: {}", t, c)
+            }
+            Self::InvalidEntryAmount(t) => write!(f, "invalid entry amount: {}", t),
+            Self::CurrencyScaleExceeded(c) => write!(f, "currency scale exceeded: {}", c),
+            Self::TransactionReversed(t) => write!(f, "transaction reversed: {}", t),
+            Self::TransactionVoided(t) => write!(f, "transaction voided: {}", t),
+            Self::InvalidReversal(t) => write!(f, "invalid reversal: {}", t),
+            Self::PeriodLocked(t) => write!(f, "transaction in locked period: {}", t),
+            Self::PeriodOutOfOrder(t) => write!(f, "period close out of order: {}", t),
+            Self::PeriodGap(t) => write!(f, "period close leaves gap: {}", t),
+            Self::TxidExists(t) => write!(f, "on-chain txid exists: {}", t),
+            Self::TransactionReconciled(t) => write!(f, "transaction reconciled: {}", t),
+            Self::UnbalancedReconciliation(a) => write!(f, "unbalanced reconciliation: {}", a),
+            Self::InvalidPayment(t) => write!(f, "invalid payment: {}", t),
+            Self::AddressIndexUsed(a, i) => write!(f, "address index used: {}/{}", a, i),
+            Self::InvalidAddressIndex(a, i) => write!(f, "invalid address index: {}/{}", a, i),
+            Self::InvalidExchangeRate(b, q) => write!(f, "invalid exchange rate: {}/{}", b, q),
+            Self::ExchangeRateExists(b, q) => write!(f, "exchange rate exists: {}/{}", b, q),
+            Self::MissingExchangeRate(b, q) => write!(f, "missing exchange rate: {}/{}", b, q),
+            Self::MissingCurrencyUnit(c, u) => write!(f, "missing currency unit: {} {}", c, u),
+            Self::InvalidCurrencyUnit(c, u) => write!(f, "invalid currency unit: {} {}", c, u),
+            Self::InvalidInvoice(t) => write!(f, "invalid invoice: {}", t),
+            Self::MissingTaxCode(c) => write!(f, "missing tax code: {}", c),
+            Self::TaxCodeExists(c) => write!(f, "tax code exists: {}", c),
+            Self::InvalidTaxCode(c) => write!(f, "invalid tax code: {}", c),
+            Self::InvalidLineItems(t) => write!(f, "invalid line items: {}", t),
             Self::MissingOrganization(o) => write!(f, "missing organization: {}", o),
             Self::OrganizationExists(o) => write!(f, "organization exists: {}", o),
         }
@@ -66,6 +145,10 @@ impl OrganizationLedgers {
         self.organization_map.contains_key(organization_id)
     }
 
+    pub fn get_organization(&self, organization_id: &OrganizationId) -> Option<&Organization> {
+        self.organization_map.get(organization_id)
+    }
+
     pub fn get_ledger(&self, organization_id: &OrganizationId) -> Result<&Ledger, Error> {
         match self.ledger_map.get(organization_id) {
             Some(ledger) => Ok(ledger),
@@ -133,6 +216,28 @@ impl OrganizationLedgers {
                 let ledger = self.get_mut_ledger(&organization_id)?;
                 ledger.add_account(account)?;
             }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: UpdateAccount { account },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.update_account(account)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action:
+                    ArchiveAccount {
+                        account_id,
+                        datetime,
+                    },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.archive_account(account_id, datetime)?;
+            }
             JournalEntry {
                 id: _,
                 version: _,
@@ -152,6 +257,15 @@ impl OrganizationLedgers {
                 let ledger = self.get_mut_ledger(&organization_id)?;
                 ledger.add_contact(contact)?;
             }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: UpdateContact { contact },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.update_contact(contact)?;
+            }
             JournalEntry {
                 id: _,
                 version: _,
@@ -167,13 +281,99 @@ impl OrganizationLedgers {
                 //     serde_json::to_string(&transaction)?,
                 //     serde_json::to_string(&ledger_entries)?
                 // );
+                if let TransactionType::Reversal { .. } = transaction.transaction_type {
+                    return Err(Error::InvalidReversal(transaction.id));
+                }
+                // payments are added with their payment record
+                if let TransactionType::InvoicePayment { .. } = transaction.transaction_type {
+                    return Err(Error::InvalidPayment(transaction.id));
+                }
                 let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.transaction_valid(&transaction, &ledger_entries)?;
                 let transaction_id = transaction.id.clone();
                 ledger.add_transaction(transaction)?;
                 let ledger_entries = ledger_entries.iter().map(|e| Arc::new(e.clone())).collect();
                 ledger.add_ledger_entries(transaction_id, &ledger_entries)?;
                 ledger.add_account_entries(&ledger_entries)
             }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: ReverseTransaction { transaction },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.reverse_transaction(transaction)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: VoidTransaction { void },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.void_transaction(void)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: ClosePeriod { period_close },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.close_period(period_close)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: Reconcile { reconciliation },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.reconcile(reconciliation)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: AddExchangeRate { exchange_rate },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.add_exchange_rate(exchange_rate)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action:
+                    AddPayment {
+                        transaction_id,
+                        payment,
+                        transaction,
+                        ledger_entries,
+                    },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.add_payment(&transaction_id, payment, transaction, ledger_entries)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: SchedulePayment { payment_schedule },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.schedule_payment(payment_schedule)?;
+            }
+            JournalEntry {
+                id: _,
+                version: _,
+                organization_id,
+                action: AddTaxCode { tax_code },
+            } => {
+                let ledger = self.get_mut_ledger(&organization_id)?;
+                ledger.add_tax_code(tax_code)?;
+            }
         }
         Ok(())
     }
@@ -182,28 +382,68 @@ impl OrganizationLedgers {
 #[derive(Clone)]
 pub struct Ledger {
     account_map: BTreeMap<AccountId, Arc<Account>>,
+    account_history_map: BTreeMap<AccountId, Vec<Arc<Account>>>,
+    archived_account_map: BTreeMap<AccountId, OffsetDateTime>,
     currency_map: BTreeMap<CurrencyId, Arc<Currency>>,
     contact_map: BTreeMap<ContactId, Arc<Contact>>,
+    contact_history_map: BTreeMap<ContactId, Vec<Arc<Contact>>>,
     transaction_map: BTreeMap<TransactionId, Arc<Transaction>>,
     transaction_entries_map: BTreeMap<TransactionId, Vec<Arc<LedgerEntry>>>,
     account_entries_map: BTreeMap<AccountId, Vec<Arc<LedgerEntry>>>,
+    reversal_map: BTreeMap<TransactionId, TransactionId>,
+    void_map: BTreeMap<TransactionId, Arc<TransactionVoid>>,
+    period_closes: Vec<Arc<PeriodClose>>,
+    txid_map: BTreeMap<(String, AccountId), TransactionId>,
+    reconciled_map: BTreeMap<(AccountId, TransactionId), Arc<Reconciliation>>,
+    address_index_map: BTreeMap<(AccountId, u32), TransactionId>,
+    exchange_rate_map:
+        BTreeMap<(CurrencyId, CurrencyId), BTreeMap<OffsetDateTime, Arc<ExchangeRate>>>,
+    invoice_payment_map: BTreeMap<TransactionId, Vec<TransactionId>>,
+    payment_schedule_map: BTreeMap<TransactionId, Vec<Arc<PaymentSchedule>>>,
+    tax_code_map: BTreeMap<String, Arc<TaxCode>>,
 }
 
 impl Ledger {
     pub fn new() -> Self {
         let account_map = BTreeMap::new();
+        let account_history_map = BTreeMap::new();
+        let archived_account_map = BTreeMap::new();
         let currency_map = BTreeMap::new();
         let contact_map = BTreeMap::new();
+        let contact_history_map = BTreeMap::new();
         let transaction_map = BTreeMap::new();
         let transaction_entries_map = BTreeMap::new();
         let account_entries_map = BTreeMap::new();
+        let reversal_map = BTreeMap::new();
+        let void_map = BTreeMap::new();
+        let period_closes = Vec::new();
+        let txid_map = BTreeMap::new();
+        let reconciled_map = BTreeMap::new();
+        let address_index_map = BTreeMap::new();
+        let exchange_rate_map = BTreeMap::new();
+        let invoice_payment_map = BTreeMap::new();
+        let payment_schedule_map = BTreeMap::new();
+        let tax_code_map = BTreeMap::new();
         Ledger {
             account_map,
+            account_history_map,
+            archived_account_map,
             currency_map,
             contact_map,
+            contact_history_map,
             transaction_map,
             transaction_entries_map,
             account_entries_map,
+            reversal_map,
+            void_map,
+            period_closes,
+            txid_map,
+            reconciled_map,
+            address_index_map,
+            exchange_rate_map,
+            invoice_payment_map,
+            payment_schedule_map,
+            tax_code_map,
         }
     }
 
@@ -216,6 +456,7 @@ impl Ledger {
 
     pub fn add_account(&mut self, account: Account) -> Result<(), Error> {
         if !self.account_map.contains_key(&account.id) {
+            self.account_valid(&account)?;
             self.account_map.insert(account.id, Arc::new(account));
             Ok(())
         } else {
@@ -223,7 +464,105 @@ impl Ledger {
         }
     }
 
+    // update account description, number or parent and keep previous version in history
+    pub fn update_account(&mut self, account: Account) -> Result<(), Error> {
+        let current = self
+            .get_account(&account.id)
+            .ok_or(Error::MissingAccount(account.id))?;
+        self.account_not_archived(&account.id)?;
+        if current.account_type != account.account_type {
+            return Err(Error::AccountTypeMismatch(account.id));
+        }
+        if current.account_category != account.account_category {
+            return Err(Error::AccountCategoryMismatch(account.id));
+        }
+        self.account_valid(&account)?;
+        self.account_history_map
+            .entry(account.id)
+            .or_default()
+            .push(current);
+        self.account_map.insert(account.id, Arc::new(account));
+        Ok(())
+    }
+
+    pub fn archive_account(
+        &mut self,
+        account_id: AccountId,
+        datetime: OffsetDateTime,
+    ) -> Result<(), Error> {
+        self.account_exists(&account_id)?;
+        self.account_not_archived(&account_id)?;
+        self.archived_account_map.insert(account_id, datetime);
+        Ok(())
+    }
+
+    pub fn account_not_archived(&self, account_id: &AccountId) -> Result<(), Error> {
+        if self.archived_account_map.contains_key(account_id) {
+            return Err(Error::AccountArchived(*account_id));
+        }
+        Ok(())
+    }
+
+    // entries dated before an account's archive date time are still allowed
+    pub fn account_not_archived_at(
+        &self,
+        account_id: &AccountId,
+        datetime: &OffsetDateTime,
+    ) -> Result<(), Error> {
+        match self.archived_account_map.get(account_id) {
+            Some(archived) if archived <= datetime => Err(Error::AccountArchived(*account_id)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_account_archived(&self, account_id: &AccountId) -> Option<OffsetDateTime> {
+        self.archived_account_map.get(account_id).cloned()
+    }
+
+    // previous versions of an account followed by the current version
+    pub fn get_account_history(&self, account_id: &AccountId) -> Vec<Arc<Account>> {
+        let mut history = self
+            .account_history_map
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default();
+        history.extend(self.get_account(account_id));
+        history
+    }
+
+    // parent must exist with the same category, no parent cycles and number unique among siblings
+    pub fn account_valid(&self, account: &Account) -> Result<(), Error> {
+        let mut ancestor_id = account.parent_id;
+        while let Some(id) = ancestor_id {
+            if id == account.id {
+                return Err(Error::AccountParentCycle(account.id));
+            }
+            ancestor_id = self.account_map.get(&id).and_then(|a| a.parent_id);
+        }
+        if let Some(parent) = self.parent(account)? {
+            self.account_not_archived(&parent.id)?;
+            if parent.account_category != account.account_category {
+                return Err(Error::AccountCategoryMismatch(account.id));
+            }
+        }
+        let number_exists = self.account_map.values().any(|a| {
+            a.id != account.id && a.parent_id == account.parent_id && a.number == account.number
+        });
+        if number_exists {
+            return Err(Error::AccountNumberExists(account.number));
+        }
+        self.account_type_valid(&account.account_type)
+    }
+
+    // units must be finer than the currency and within the decimal scale limit
     pub fn add_currency(&mut self, currency: Currency) -> Result<(), Error> {
+        if let Some(unit) = currency
+            .units
+            .iter()
+            .find(|unit| unit.scale < currency.scale || unit.scale > MAX_CURRENCY_SCALE)
+        {
+            return Err(Error::InvalidCurrencyUnit(currency.id, unit.code.clone()));
+        }
         if !self.currency_map.contains_key(&currency.id) {
             self.currency_map.insert(currency.id, Arc::new(currency));
             Ok(())
@@ -239,6 +578,123 @@ impl Ledger {
         Ok(())
     }
 
+    pub fn add_exchange_rate(&mut self, exchange_rate: ExchangeRate) -> Result<(), Error> {
+        let base_currency_id = exchange_rate.base_currency_id;
+        let quote_currency_id = exchange_rate.quote_currency_id;
+        self.currency_exists(&base_currency_id)?;
+        self.currency_exists(&quote_currency_id)?;
+        if base_currency_id == quote_currency_id || exchange_rate.rate <= Decimal::ZERO {
+            return Err(Error::InvalidExchangeRate(
+                base_currency_id,
+                quote_currency_id,
+            ));
+        }
+        let rates = self
+            .exchange_rate_map
+            .entry((base_currency_id, quote_currency_id))
+            .or_default();
+        if rates.contains_key(&exchange_rate.datetime) {
+            return Err(Error::ExchangeRateExists(
+                base_currency_id,
+                quote_currency_id,
+            ));
+        }
+        rates.insert(exchange_rate.datetime, Arc::new(exchange_rate));
+        Ok(())
+    }
+
+    pub fn add_tax_code(&mut self, tax_code: TaxCode) -> Result<(), Error> {
+        self.account_exists(&tax_code.account_id)?;
+        if tax_code.rate < Decimal::ZERO {
+            return Err(Error::InvalidTaxCode(tax_code.code));
+        }
+        if self.tax_code_map.contains_key(&tax_code.code) {
+            return Err(Error::TaxCodeExists(tax_code.code));
+        }
+        self.tax_code_map
+            .insert(tax_code.code.clone(), Arc::new(tax_code));
+        Ok(())
+    }
+
+    pub fn get_tax_code(&self, code: &str) -> Option<Arc<TaxCode>> {
+        self.tax_code_map.get(code).cloned()
+    }
+
+    pub fn tax_codes(&self) -> Vec<Arc<TaxCode>> {
+        self.tax_code_map.values().cloned().collect()
+    }
+
+    // most recent exchange rate of the currency pair at or before date time
+    pub fn get_exchange_rate(
+        &self,
+        base_currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        datetime: &OffsetDateTime,
+    ) -> Option<Arc<ExchangeRate>> {
+        self.exchange_rate_map
+            .get(&(*base_currency_id, *quote_currency_id))
+            .and_then(|rates| rates.range(..=*datetime).next_back())
+            .map(|(_, exchange_rate)| exchange_rate.clone())
+    }
+
+    // rate from the pair's most recent rate or the inverse of the reverse pair's, whichever is
+    // more recent
+    pub fn exchange_rate(
+        &self,
+        base_currency_id: &CurrencyId,
+        quote_currency_id: &CurrencyId,
+        datetime: &OffsetDateTime,
+    ) -> Option<Decimal> {
+        if base_currency_id == quote_currency_id {
+            return Some(Decimal::ONE);
+        }
+        let rate = self.get_exchange_rate(base_currency_id, quote_currency_id, datetime);
+        let inverse_rate = self.get_exchange_rate(quote_currency_id, base_currency_id, datetime);
+        match (rate, inverse_rate) {
+            (Some(rate), Some(inverse_rate)) if inverse_rate.datetime > rate.datetime => {
+                Some(Decimal::ONE / inverse_rate.rate)
+            }
+            (Some(rate), _) => Some(rate.rate),
+            (None, Some(inverse_rate)) => Some(Decimal::ONE / inverse_rate.rate),
+            (None, None) => None,
+        }
+    }
+
+    // amount converted at the exchange rate at date time, rounded to the currency's scale
+    pub fn convert(
+        &self,
+        currency_amount: &CurrencyAmount,
+        currency_id: &CurrencyId,
+        datetime: &OffsetDateTime,
+    ) -> Result<CurrencyAmount, Error> {
+        let currency = self
+            .get_currency(currency_id)
+            .ok_or(Error::MissingCurrency(*currency_id))?;
+        let rate = self
+            .exchange_rate(&currency_amount.currency_id, currency_id, datetime)
+            .ok_or(Error::MissingExchangeRate(
+                currency_amount.currency_id,
+                *currency_id,
+            ))?;
+        let amount = (currency_amount.amount * rate).round_dp(currency.scale);
+        Ok(CurrencyAmount::new(currency_id, amount))
+    }
+
+    // amount in any declared unit of a currency converted to currency units
+    pub fn unit_amount(&self, unit_amount: &UnitAmount) -> Result<CurrencyAmount, Error> {
+        let currency_id = unit_amount.currency_id;
+        let currency = self
+            .get_currency(&currency_id)
+            .ok_or(Error::MissingCurrency(currency_id))?;
+        let amount = match &unit_amount.unit {
+            Some(unit) => currency
+                .from_unit(unit_amount.amount, unit)
+                .ok_or(Error::MissingCurrencyUnit(currency_id, unit.clone()))?,
+            None => unit_amount.amount,
+        };
+        Ok(CurrencyAmount::new(&currency_id, amount))
+    }
+
     pub fn add_contact(&mut self, contact: Contact) -> Result<(), Error> {
         if !self.contact_map.contains_key(&contact.id) {
             self.contact_map.insert(contact.id, Arc::new(contact));
@@ -248,6 +704,30 @@ impl Ledger {
         }
     }
 
+    // update contact and keep previous version in history
+    pub fn update_contact(&mut self, contact: Contact) -> Result<(), Error> {
+        let current = self
+            .get_contact(&contact.id)
+            .ok_or(Error::MissingContact(contact.id))?;
+        self.contact_history_map
+            .entry(contact.id)
+            .or_default()
+            .push(current);
+        self.contact_map.insert(contact.id, Arc::new(contact));
+        Ok(())
+    }
+
+    // previous versions of a contact followed by the current version
+    pub fn get_contact_history(&self, contact_id: &ContactId) -> Vec<Arc<Contact>> {
+        let mut history = self
+            .contact_history_map
+            .get(contact_id)
+            .cloned()
+            .unwrap_or_default();
+        history.extend(self.get_contact(contact_id));
+        history
+    }
+
     pub fn contact_exists(&self, contact_id: &ContactId) -> Result<(), Error> {
         if !self.contact_map.contains_key(&contact_id) {
             return Err(Error::MissingContact(contact_id.clone()));
@@ -257,6 +737,20 @@ impl Ledger {
 
     pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
         if !self.transaction_map.contains_key(&transaction.id) {
+            if let Some((account_id, index)) = invoice_address_index(&transaction) {
+                self.address_index_map
+                    .insert((account_id, index), transaction.id);
+            }
+            if let TransactionType::InvoicePayment { transaction_id }
+            | TransactionType::WriteOff { transaction_id }
+            | TransactionType::LateFee { transaction_id }
+            | TransactionType::Discount { transaction_id } = &transaction.transaction_type
+            {
+                self.invoice_payment_map
+                    .entry(*transaction_id)
+                    .or_default()
+                    .push(transaction.id);
+            }
             self.transaction_map
                 .insert(transaction.id.clone(), Arc::new(transaction));
             Ok(())
@@ -271,6 +765,12 @@ impl Ledger {
         ledger_entries: &Vec<Arc<LedgerEntry>>,
     ) -> Result<(), Error> {
         if !self.transaction_entries_map.contains_key(&transaction_id) {
+            let transaction = self
+                .get_transaction(&transaction_id)
+                .ok_or(Error::MissingTransaction(transaction_id))?;
+            for txid_account in self.txid_accounts(&transaction, ledger_entries) {
+                self.txid_map.insert(txid_account, transaction_id);
+            }
             let entries: Vec<Arc<LedgerEntry>> = ledger_entries
                 .iter()
                 .cloned()
@@ -283,100 +783,713 @@ impl Ledger {
         }
     }
 
-    pub fn transaction_exists(&self, transaction_id: &TransactionId) -> Result<(), Error> {
-        if !self.transaction_map.contains_key(&transaction_id) {
-            return Err(Error::MissingTransaction(transaction_id.clone()));
+    // post a payment transaction and append the payment to the paid invoice
+    pub fn add_payment(
+        &mut self,
+        invoice_id: &TransactionId,
+        payment: Payment,
+        transaction: Transaction,
+        ledger_entries: Vec<LedgerEntry>,
+    ) -> Result<(), Error> {
+        match &transaction.transaction_type {
+            TransactionType::InvoicePayment { transaction_id } if transaction_id == invoice_id => {}
+            _ => return Err(Error::InvalidPayment(transaction.id)),
+        }
+        lightning::payment_valid(&payment).map_err(|_| Error::InvalidPayment(transaction.id))?;
+        self.transaction_valid(&transaction, &ledger_entries)?;
+        let (balance_type, contact_account_ids) = invoice::contact_account_ids(self, invoice_id);
+        let settled = |currency_id: &CurrencyId| -> Decimal {
+            ledger_entries
+                .iter()
+                .filter(|entry| {
+                    entry.currency_amount.currency_id == *currency_id
+                        && contact_account_ids.contains(&entry.account_id)
+                })
+                .map(|entry| -balance_type.amount(entry))
+                .sum()
+        };
+        if let Some(payment_amount) = payment_amount(&payment) {
+            if settled(&payment_amount.currency_id) != payment_amount.amount {
+                return Err(Error::InvalidPayment(transaction.id));
+            }
+        }
+        // payments may not settle more than the outstanding balance
+        let balance = invoice::InvoiceBalance::new(self, invoice_id, transaction.datetime)?;
+        if settled(&balance.currency_id) > balance.outstanding {
+            return Err(Error::InvalidPayment(transaction.id));
+        }
+
+        let mut invoice = self
+            .get_transaction(invoice_id)
+            .expect("invoice")
+            .as_ref()
+            .clone();
+        if let TransactionType::Invoice { payments, .. } | TransactionType::Bill { payments, .. } =
+            &mut invoice.transaction_type
+        {
+            payments.push(payment);
         }
+        let transaction_id = transaction.id;
+        self.add_transaction(transaction)?;
+        let ledger_entries = ledger_entries.into_iter().map(Arc::new).collect();
+        self.add_ledger_entries(transaction_id, &ledger_entries)?;
+        self.add_account_entries(&ledger_entries);
+        self.transaction_map.insert(*invoice_id, Arc::new(invoice));
         Ok(())
     }
 
-    pub fn account_type_valid(&self, account_type: &AccountType) -> Result<(), Error> {
-        match account_type {
-            AccountType::ContactAccount { contact_id } => self.contact_exists(contact_id),
-            AccountType::BankAccount { currency_id, .. } => self.currency_exists(currency_id),
-            _ => Ok(()),
+    // planned payment of an unpaid bill
+    pub fn schedule_payment(&mut self, payment_schedule: PaymentSchedule) -> Result<(), Error> {
+        let transaction_id = payment_schedule.transaction_id;
+        self.invoice_valid(&transaction_id)?;
+        let bill = self.get_transaction(&transaction_id).expect("bill");
+        if !matches!(bill.transaction_type, TransactionType::Bill { .. }) {
+            return Err(Error::InvalidInvoice(transaction_id));
         }
+        self.currency_exists(&payment_schedule.currency_amount.currency_id)?;
+        self.payment_schedule_map
+            .entry(transaction_id)
+            .or_default()
+            .push(Arc::new(payment_schedule));
+        Ok(())
     }
 
-    pub fn parent<'a>(&'a self, account: &'a Account) -> Result<Option<&Account>, Error> {
-        let parent_id: Option<AccountId> = account.parent_id;
-        match parent_id {
-            Some(id) => {
-                if let Some(account) = self.account_map.get(&id) {
-                    Ok(Some(account))
-                } else {
-                    Err(Error::MissingAccount(id))
-                }
-            }
-            None => Ok(None),
+    pub fn get_payment_schedules(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Vec<Arc<PaymentSchedule>> {
+        self.payment_schedule_map
+            .get(transaction_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // invoice or bill that is not voided or reversed
+    pub fn invoice_valid(&self, transaction_id: &TransactionId) -> Result<(), Error> {
+        let invoice = self
+            .get_transaction(transaction_id)
+            .ok_or(Error::MissingTransaction(*transaction_id))?;
+        if !matches!(
+            invoice.transaction_type,
+            TransactionType::Invoice { .. } | TransactionType::Bill { .. }
+        ) || self.reversal_map.contains_key(transaction_id)
+            || self.void_map.contains_key(transaction_id)
+        {
+            return Err(Error::InvalidInvoice(*transaction_id));
         }
+        Ok(())
     }
 
-    pub fn children<'a>(&'a self, account_id: &'a AccountId) -> Vec<Arc<Account>> {
-        self.account_map
-            .values()
-            .filter(|a| {
-                if let Some(parent_id) = a.parent_id {
-                    &parent_id == account_id
-                } else {
-                    false
-                }
-            })
-            .cloned()
+    // payment, write off, late fee and discount transactions of an invoice
+    pub fn get_invoice_payments(&self, invoice_id: &TransactionId) -> Vec<Arc<Transaction>> {
+        self.invoice_payment_map
+            .get(invoice_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|transaction_id| self.get_transaction(transaction_id))
             .collect()
     }
 
-    pub fn child_ids<'a>(&'a self, account: &'a Arc<Account>) -> Vec<AccountId> {
-        self.children(&account.id).iter().map(|c| c.id).collect()
+    // next unused receive address derivation index of a bitcoin account
+    pub fn next_address_index(&self, account_id: &AccountId) -> u32 {
+        self.address_index_map
+            .range((*account_id, 0)..=(*account_id, u32::MAX))
+            .next_back()
+            .map(|((_, index), _)| index + 1)
+            .unwrap_or(0)
     }
 
-    pub fn full_number(&self, account: &Account) -> Result<Vec<AccountNumber>, Error> {
-        let parent_opt = self.parent(account)?;
-        match parent_opt {
-            Some(parent) => {
-                let mut full_number = self.full_number(parent)?;
-                full_number.push(account.number);
-                Ok(full_number)
-            }
-            None => Ok(vec![account.number]),
-        }
+    pub fn get_address_index_transaction(
+        &self,
+        account_id: &AccountId,
+        index: u32,
+    ) -> Option<Arc<Transaction>> {
+        self.address_index_map
+            .get(&(*account_id, index))
+            .and_then(|transaction_id| self.get_transaction(transaction_id))
     }
 
-    pub fn get_account(&self, id: &AccountId) -> Option<Arc<Account>> {
-        self.account_map.get(id).cloned()
+    // on-chain transaction of a txid with an entry in the bitcoin account
+    pub fn get_txid_transaction(
+        &self,
+        account_id: &AccountId,
+        txid: &str,
+    ) -> Option<Arc<Transaction>> {
+        self.txid_map
+            .get(&(txid.to_string(), *account_id))
+            .and_then(|transaction_id| self.get_transaction(transaction_id))
     }
 
-    pub fn get_root_account(&self, category: AccountCategory) -> Option<AccountId> {
-        self.account_map
-            .values()
-            .filter(|account| account.parent_id.is_none())
-            .find_map(|account| {
-                if account.account_category.eq(&category) {
-                    Some(account.id.clone())
-                } else {
-                    None
-                }
+    // txid and bitcoin accounts of an on-chain transaction's entries
+    fn txid_accounts<E: Borrow<LedgerEntry>>(
+        &self,
+        transaction: &Transaction,
+        ledger_entries: &[E],
+    ) -> Vec<(String, AccountId)> {
+        let txid = match &transaction.transaction_type {
+            TransactionType::OnChain { txid } => txid,
+            _ => return Vec::new(),
+        };
+        let mut txid_accounts: Vec<(String, AccountId)> = ledger_entries
+            .iter()
+            .map(|entry| entry.borrow().account_id)
+            .filter(|account_id| {
+                self.get_account(account_id).is_some_and(|account| {
+                    matches!(account.account_type, AccountType::BitcoinAccount { .. })
+                })
             })
+            .map(|account_id| (txid.clone(), account_id))
+            .collect();
+        txid_accounts.sort();
+        txid_accounts.dedup();
+        txid_accounts
     }
 
-    pub fn accounts(&self) -> Vec<Arc<Account>> {
-        self.account_map.values().cloned().collect()
+    pub fn transaction_exists(&self, transaction_id: &TransactionId) -> Result<(), Error> {
+        if !self.transaction_map.contains_key(&transaction_id) {
+            return Err(Error::MissingTransaction(transaction_id.clone()));
+        }
+        Ok(())
     }
 
-    pub fn get_currency(&self, id: &CurrencyId) -> Option<Arc<Currency>> {
-        self.currency_map.get(id).cloned()
+    // transaction can only be reversed or voided once and reversals can not be reversed or voided
+    pub fn transaction_reversible(&self, transaction_id: &TransactionId) -> Result<(), Error> {
+        let transaction = self
+            .get_transaction(transaction_id)
+            .ok_or(Error::MissingTransaction(*transaction_id))?;
+        if let TransactionType::Reversal { .. } = transaction.transaction_type {
+            return Err(Error::InvalidReversal(*transaction_id));
+        }
+        if self.reversal_map.contains_key(transaction_id) {
+            return Err(Error::TransactionReversed(*transaction_id));
+        }
+        if self.void_map.contains_key(transaction_id) {
+            return Err(Error::TransactionVoided(*transaction_id));
+        }
+        Ok(())
     }
 
-    pub fn currencies(&self) -> Vec<Arc<Currency>> {
-        self.currency_map.values().cloned().collect()
+    // post mirror image ledger entries of the reversed transaction
+    pub fn reverse_transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
+        let reversed_id = match transaction.transaction_type {
+            TransactionType::Reversal { transaction_id } => transaction_id,
+            _ => return Err(Error::InvalidReversal(transaction.id)),
+        };
+        self.transaction_reversible(&reversed_id)?;
+        let ledger_entries: Vec<LedgerEntry> = self
+            .get_transaction_entries(&reversed_id)
+            .iter()
+            .flatten()
+            .map(|entry| {
+                let entry_type = match entry.entry_type {
+                    EntryType::Debit => EntryType::Credit,
+                    EntryType::Credit => EntryType::Debit,
+                };
+                LedgerEntry::new(
+                    &transaction.id,
+                    entry_type,
+                    &entry.account_id,
+                    entry.currency_amount.clone(),
+                    entry.description.clone(),
+                )
+            })
+            .collect();
+        self.transaction_valid(&transaction, &ledger_entries)?;
+        let transaction_id = transaction.id;
+        self.add_transaction(transaction)?;
+        let ledger_entries = ledger_entries.into_iter().map(Arc::new).collect();
+        self.add_ledger_entries(transaction_id, &ledger_entries)?;
+        self.add_account_entries(&ledger_entries);
+        self.reversal_map.insert(reversed_id, transaction_id);
+        Ok(())
     }
 
-    pub fn get_contact(&self, id: &ContactId) -> Option<Arc<Contact>> {
-        self.contact_map.get(id).cloned()
+    pub fn void_transaction(&mut self, void: TransactionVoid) -> Result<(), Error> {
+        self.transaction_reversible(&void.transaction_id)?;
+        let transaction = self
+            .get_transaction(&void.transaction_id)
+            .expect("transaction");
+        if self.period_locked(&transaction.datetime) {
+            return Err(Error::PeriodLocked(transaction.id));
+        }
+        self.void_map.insert(void.transaction_id, Arc::new(void));
+        Ok(())
     }
 
-    pub fn contacts(&self) -> Vec<Arc<Contact>> {
-        self.contact_map.values().cloned().collect()
+    pub fn period_locked(&self, datetime: &OffsetDateTime) -> bool {
+        self.period_closes
+            .iter()
+            .any(|period_close| period_close.contains(datetime))
+    }
+
+    // start of the period following the last period closed before date time
+    pub fn current_period_start(&self, datetime: &OffsetDateTime) -> Option<OffsetDateTime> {
+        self.period_closes
+            .iter()
+            .map(|period_close| period_close.end_date_time)
+            .filter(|end_date_time| end_date_time < datetime)
+            .max()
+            .map(|end_date_time| end_date_time + Duration::nanoseconds(1))
+    }
+
+    pub fn period_closes(&self) -> Vec<Arc<PeriodClose>> {
+        self.period_closes.clone()
+    }
+
+    // zero income statement account balances into retained earnings and lock the period
+    pub fn close_period(&mut self, period_close: PeriodClose) -> Result<(), Error> {
+        let retained_earnings_id = period_close.retained_earnings_account_id;
+        let retained_earnings = self
+            .get_account(&retained_earnings_id)
+            .ok_or(Error::MissingAccount(retained_earnings_id))?;
+        if retained_earnings.account_category
+            != AccountCategory::BalanceSheet(BalanceSheetCategory::Equity)
+        {
+            return Err(Error::AccountCategoryMismatch(retained_earnings_id));
+        }
+        let overlaps = self.period_closes.iter().any(|locked| {
+            locked.start_date_time <= period_close.end_date_time
+                && period_close.start_date_time <= locked.end_date_time
+        });
+        if overlaps {
+            return Err(Error::PeriodLocked(period_close.transaction_id));
+        }
+        // periods are closed in order, each starting right after the last closed period
+        if let Some(last_end) = self
+            .period_closes
+            .iter()
+            .map(|locked| locked.end_date_time)
+            .max()
+        {
+            if period_close.end_date_time < last_end {
+                return Err(Error::PeriodOutOfOrder(period_close.transaction_id));
+            }
+            if period_close.start_date_time != last_end + Duration::nanoseconds(1) {
+                return Err(Error::PeriodGap(period_close.transaction_id));
+            }
+        }
+
+        let transaction_id = period_close.transaction_id;
+        let mut earnings: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+        let mut ledger_entries: Vec<LedgerEntry> = Vec::new();
+        for account in self.account_map.values() {
+            if let AccountCategory::BalanceSheet(_) = account.account_category {
+                continue;
+            }
+            let mut balances: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+            for entry in self.get_account_entries_period(
+                &account.id,
+                Some(&period_close.start_date_time),
+                &period_close.end_date_time,
+            ) {
+                let balance = balances
+                    .entry(entry.currency_amount.currency_id)
+                    .or_default();
+                match entry.entry_type {
+                    EntryType::Debit => *balance += entry.currency_amount.amount,
+                    EntryType::Credit => *balance -= entry.currency_amount.amount,
+                }
+            }
+            for (currency_id, balance) in balances {
+                if balance.is_zero() {
+                    continue;
+                }
+                *earnings.entry(currency_id).or_default() += balance;
+                ledger_entries.push(self.closing_entry(
+                    &transaction_id,
+                    &account.id,
+                    &currency_id,
+                    -balance,
+                )?);
+            }
+        }
+        for (currency_id, earning) in earnings {
+            if !earning.is_zero() {
+                ledger_entries.push(self.closing_entry(
+                    &transaction_id,
+                    &retained_earnings_id,
+                    &currency_id,
+                    earning,
+                )?);
+            }
+        }
+
+        if !ledger_entries.is_empty() {
+            let transaction = Transaction {
+                id: transaction_id,
+                datetime: period_close.end_date_time,
+                description: format!(
+                    "Period close {} to {}",
+                    period_close.start_date_time.date(),
+                    period_close.end_date_time.date()
+                ),
+                transaction_type: TransactionType::PeriodClose,
+            };
+            self.transaction_valid(&transaction, &ledger_entries)?;
+            self.add_transaction(transaction)?;
+            let ledger_entries = ledger_entries.into_iter().map(Arc::new).collect();
+            self.add_ledger_entries(transaction_id, &ledger_entries)?;
+            self.add_account_entries(&ledger_entries);
+        }
+        self.period_closes.push(Arc::new(period_close));
+        Ok(())
+    }
+
+    // debit a positive amount or credit a negative amount, balances of payment rail entries may
+    // be in a currency's finest unit
+    fn closing_entry(
+        &self,
+        transaction_id: &TransactionId,
+        account_id: &AccountId,
+        currency_id: &CurrencyId,
+        amount: Decimal,
+    ) -> Result<LedgerEntry, Error> {
+        let currency = self
+            .get_currency(currency_id)
+            .ok_or(Error::MissingCurrency(*currency_id))?;
+        let money = Money::with_scale(&currency, amount, currency.finest_scale())
+            .map_err(|_| Error::CurrencyScaleExceeded(*currency_id))?;
+        Ok(LedgerEntry::from_money(
+            transaction_id,
+            account_id,
+            &money,
+            Some("Period close".to_string()),
+        ))
+    }
+
+    pub fn get_reversal(&self, transaction_id: &TransactionId) -> Option<Arc<Transaction>> {
+        self.reversal_map
+            .get(transaction_id)
+            .and_then(|reversal_id| self.get_transaction(reversal_id))
+    }
+
+    pub fn get_void(&self, transaction_id: &TransactionId) -> Option<Arc<TransactionVoid>> {
+        self.void_map.get(transaction_id).cloned()
+    }
+
+    // mark account transactions as reconciled, each transaction at most once per account, the
+    // account balance must differ from the external balance by exactly the recorded difference
+    pub fn reconcile(&mut self, reconciliation: Reconciliation) -> Result<(), Error> {
+        let account_id = reconciliation.account_id;
+        let account = self
+            .get_account(&account_id)
+            .ok_or(Error::MissingAccount(account_id))?;
+        if let Some(balance) = &reconciliation.balance {
+            let account_balance =
+                report::AccountTotals::new(self, account, reconciliation.datetime)
+                    .balance(&balance.currency_id);
+            if account_balance - balance.amount != reconciliation.difference {
+                return Err(Error::UnbalancedReconciliation(account_id));
+            }
+        }
+        for transaction_id in &reconciliation.transaction_ids {
+            self.transaction_exists(transaction_id)?;
+            if self.void_map.contains_key(transaction_id) {
+                return Err(Error::TransactionVoided(*transaction_id));
+            }
+            let account_entry_exists = self
+                .get_transaction_entries(transaction_id)
+                .iter()
+                .flatten()
+                .any(|entry| entry.account_id == account_id);
+            if !account_entry_exists {
+                return Err(Error::MissingLedgerEntries(*transaction_id));
+            }
+            if self
+                .reconciled_map
+                .contains_key(&(account_id, *transaction_id))
+            {
+                return Err(Error::TransactionReconciled(*transaction_id));
+            }
+        }
+        let reconciliation = Arc::new(reconciliation);
+        for transaction_id in &reconciliation.transaction_ids {
+            self.reconciled_map
+                .insert((account_id, *transaction_id), reconciliation.clone());
+        }
+        Ok(())
+    }
+
+    pub fn get_reconciliation(
+        &self,
+        account_id: &AccountId,
+        transaction_id: &TransactionId,
+    ) -> Option<Arc<Reconciliation>> {
+        self.reconciled_map
+            .get(&(*account_id, *transaction_id))
+            .cloned()
+    }
+
+    // debits and credits must balance per currency and reference known accounts and currencies
+    pub fn transaction_valid(
+        &self,
+        transaction: &Transaction,
+        ledger_entries: &Vec<LedgerEntry>,
+    ) -> Result<(), Error> {
+        if ledger_entries.is_empty() {
+            return Err(Error::MissingLedgerEntries(transaction.id));
+        }
+        if self.period_locked(&transaction.datetime) {
+            return Err(Error::PeriodLocked(transaction.id));
+        }
+        self.payments_valid(transaction, ledger_entries)?;
+        for (txid, account_id) in self.txid_accounts(transaction, ledger_entries) {
+            if self.txid_map.contains_key(&(txid.clone(), account_id)) {
+                return Err(Error::TxidExists(txid));
+            }
+        }
+        let mut balances: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+        for entry in ledger_entries {
+            if entry.transaction_id != transaction.id {
+                return Err(Error::TransactionIdMismatch(entry.transaction_id));
+            }
+            self.account_exists(&entry.account_id)?;
+            self.account_not_archived_at(&entry.account_id, &transaction.datetime)?;
+            let currency_id = entry.currency_amount.currency_id;
+            let currency = self
+                .get_currency(&currency_id)
+                .ok_or(Error::MissingCurrency(currency_id))?;
+            let amount = entry
+                .money(&currency, entry_scale(self, transaction, &currency))
+                .map_err(|_| Error::CurrencyScaleExceeded(currency_id))?
+                .amount;
+            // debits and credits are given by entry type, amounts are always positive
+            if amount <= Decimal::ZERO {
+                return Err(Error::InvalidEntryAmount(transaction.id));
+            }
+            let balance = balances.entry(currency_id).or_default();
+            match entry.entry_type {
+                EntryType::Debit => *balance += amount,
+                EntryType::Credit => *balance -= amount,
+            }
+        }
+        if let TransactionType::Bill { .. } = transaction.transaction_type {
+            let payable = ledger_entries.iter().any(|entry| {
+                entry.entry_type == EntryType::Credit
+                    && self.get_account(&entry.account_id).is_some_and(|account| {
+                        matches!(account.account_type, AccountType::ContactAccount { .. })
+                            && account.account_category
+                                == AccountCategory::BalanceSheet(BalanceSheetCategory::Liability)
+                    })
+            });
+            if !payable {
+                return Err(Error::InvalidInvoice(transaction.id));
+            }
+        }
+        invoice::line_items_valid(self, transaction, ledger_entries)?;
+        match balances.iter().find(|(_, balance)| !balance.is_zero()) {
+            Some((currency_id, _)) => {
+                Err(Error::UnbalancedLedgerEntries(transaction.id, *currency_id))
+            }
+            None => Ok(()),
+        }
+    }
+
+    // lightning invoices must parse and lightning payments must match their invoice, write offs,
+    // late fees and discounts must change the invoice balance owed without settling more than
+    // the outstanding balance
+    pub fn payments_valid(
+        &self,
+        transaction: &Transaction,
+        ledger_entries: &[LedgerEntry],
+    ) -> Result<(), Error> {
+        match &transaction.transaction_type {
+            TransactionType::InvoicePayment { transaction_id } => {
+                self.invoice_valid(transaction_id)?;
+            }
+            TransactionType::WriteOff { transaction_id }
+            | TransactionType::LateFee { transaction_id }
+            | TransactionType::Discount { transaction_id } => {
+                self.invoice_valid(transaction_id)?;
+                let (balance_type, contact_account_ids) =
+                    invoice::contact_account_ids(self, transaction_id);
+                let balance =
+                    invoice::InvoiceBalance::new(self, transaction_id, transaction.datetime)?;
+                let contact_entries: Vec<&LedgerEntry> = ledger_entries
+                    .iter()
+                    .filter(|entry| contact_account_ids.contains(&entry.account_id))
+                    .collect();
+                if contact_entries.is_empty()
+                    || contact_entries
+                        .iter()
+                        .any(|entry| entry.currency_amount.currency_id != balance.currency_id)
+                {
+                    return Err(Error::InvalidPayment(transaction.id));
+                }
+                let owed: Decimal = contact_entries
+                    .iter()
+                    .map(|entry| balance_type.amount(entry))
+                    .sum();
+                let valid = match transaction.transaction_type {
+                    TransactionType::LateFee { .. } => owed > Decimal::ZERO,
+                    _ => owed < Decimal::ZERO && -owed <= balance.outstanding,
+                };
+                if !valid {
+                    return Err(Error::InvalidPayment(transaction.id));
+                }
+            }
+            _ => {}
+        }
+        if let TransactionType::Invoice {
+            payment_method,
+            payments,
+            ..
+        }
+        | TransactionType::Bill {
+            payment_method,
+            payments,
+            ..
+        } = &transaction.transaction_type
+        {
+            match payment_method {
+                PaymentMethod::Lightning { invoice } => {
+                    Bolt11Invoice::parse(invoice)
+                        .map_err(|_| Error::InvalidPayment(transaction.id))?;
+                }
+                // a bill's bitcoin address is the vendor's, only invoices receive to our accounts
+                PaymentMethod::Bitcoin {
+                    account_id: Some(account_id),
+                    derivation_index,
+                    ..
+                } if matches!(
+                    transaction.transaction_type,
+                    TransactionType::Invoice { .. }
+                ) =>
+                {
+                    let account = self
+                        .get_account(account_id)
+                        .ok_or(Error::MissingAccount(*account_id))?;
+                    if !matches!(account.account_type, AccountType::BitcoinAccount { .. }) {
+                        return Err(Error::AccountTypeMismatch(*account_id));
+                    }
+                    if let Some(index) = derivation_index {
+                        if self.address_index_map.contains_key(&(*account_id, *index)) {
+                            return Err(Error::AddressIndexUsed(*account_id, *index));
+                        }
+                        // addresses are used in derivation order so wallet scans within the
+                        // gap limit find every invoice payment
+                        if *index != self.next_address_index(account_id) {
+                            return Err(Error::InvalidAddressIndex(*account_id, *index));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            for payment in payments {
+                lightning::payment_valid(payment)
+                    .map_err(|_| Error::InvalidPayment(transaction.id))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn account_type_valid(&self, account_type: &AccountType) -> Result<(), Error> {
+        match account_type {
+            AccountType::ContactAccount { contact_id } => self.contact_exists(contact_id),
+            AccountType::BankAccount { currency_id, .. } => self.currency_exists(currency_id),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn parent<'a>(&'a self, account: &'a Account) -> Result<Option<&Account>, Error> {
+        let parent_id: Option<AccountId> = account.parent_id;
+        match parent_id {
+            Some(id) => {
+                if let Some(account) = self.account_map.get(&id) {
+                    Ok(Some(account))
+                } else {
+                    Err(Error::MissingAccount(id))
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn children<'a>(&'a self, account_id: &'a AccountId) -> Vec<Arc<Account>> {
+        self.account_map
+            .values()
+            .filter(|a| {
+                if let Some(parent_id) = a.parent_id {
+                    &parent_id == account_id
+                } else {
+                    false
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn child_ids<'a>(&'a self, account: &'a Arc<Account>) -> Vec<AccountId> {
+        self.children(&account.id).iter().map(|c| c.id).collect()
+    }
+
+    pub fn descendant_ids(&self, account_id: &AccountId) -> Vec<AccountId> {
+        self.children(account_id)
+            .iter()
+            .flat_map(|child| {
+                let mut ids = vec![child.id];
+                ids.append(&mut self.descendant_ids(&child.id));
+                ids
+            })
+            .collect()
+    }
+
+    pub fn full_number(&self, account: &Account) -> Result<Vec<AccountNumber>, Error> {
+        let parent_opt = self.parent(account)?;
+        match parent_opt {
+            Some(parent) => {
+                let mut full_number = self.full_number(parent)?;
+                full_number.push(account.number);
+                Ok(full_number)
+            }
+            None => Ok(vec![account.number]),
+        }
+    }
+
+    pub fn get_account(&self, id: &AccountId) -> Option<Arc<Account>> {
+        self.account_map.get(id).cloned()
+    }
+
+    pub fn get_root_account(&self, category: AccountCategory) -> Option<AccountId> {
+        self.account_map
+            .values()
+            .filter(|account| account.parent_id.is_none())
+            .find_map(|account| {
+                if account.account_category.eq(&category) {
+                    Some(account.id.clone())
+                } else {
+                    None
+                }
+            })
+    }
+
+    pub fn get_root_accounts(&self, category: AccountCategory) -> Vec<AccountId> {
+        self.account_map
+            .values()
+            .filter(|account| account.parent_id.is_none())
+            .filter(|account| account.account_category.eq(&category))
+            .map(|account| account.id)
+            .collect()
+    }
+
+    pub fn accounts(&self) -> Vec<Arc<Account>> {
+        self.account_map.values().cloned().collect()
+    }
+
+    pub fn get_currency(&self, id: &CurrencyId) -> Option<Arc<Currency>> {
+        self.currency_map.get(id).cloned()
+    }
+
+    pub fn currencies(&self) -> Vec<Arc<Currency>> {
+        self.currency_map.values().cloned().collect()
+    }
+
+    pub fn get_contact(&self, id: &ContactId) -> Option<Arc<Contact>> {
+        self.contact_map.get(id).cloned()
+    }
+
+    pub fn contacts(&self) -> Vec<Arc<Contact>> {
+        self.contact_map.values().cloned().collect()
     }
 
     pub fn get_transaction(&self, id: &TransactionId) -> Option<Arc<Transaction>> {
@@ -387,10 +1500,41 @@ impl Ledger {
         self.transaction_map.values().cloned().collect()
     }
 
+    pub fn get_transaction_entries(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Option<Vec<Arc<LedgerEntry>>> {
+        self.transaction_entries_map.get(transaction_id).cloned()
+    }
+
     pub fn get_account_entries(&self, account_id: &AccountId) -> Option<Vec<Arc<LedgerEntry>>> {
         self.account_entries_map.get(account_id).cloned()
     }
 
+    // account entries for transactions at or after optional start and at or before end date time,
+    // excluding voided transactions
+    pub fn get_account_entries_period(
+        &self,
+        account_id: &AccountId,
+        start_date_time: Option<&OffsetDateTime>,
+        end_date_time: &OffsetDateTime,
+    ) -> Vec<Arc<LedgerEntry>> {
+        self.get_account_entries(account_id)
+            .iter()
+            .flatten()
+            .filter(|entry| {
+                self.get_transaction(&entry.transaction_id)
+                    .filter(|transaction| !self.void_map.contains_key(&transaction.id))
+                    .map(|transaction| {
+                        start_date_time.is_none_or(|start| transaction.datetime >= *start)
+                            && transaction.datetime <= *end_date_time
+                    })
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
     pub fn add_account_entries(&mut self, entries: &Vec<Arc<LedgerEntry>>) {
         for entry in entries.iter().cloned() {
             let account_id = &entry.account_id;
@@ -407,6 +1551,89 @@ impl Ledger {
     }
 }
 
+// currency amount of fiat and lightning payments, lightning amounts in exact millisatoshis
+fn payment_amount(payment: &Payment) -> Option<CurrencyAmount> {
+    match payment {
+        Payment::Bitcoin { .. } => None,
+        Payment::Lightning { amount_msat, .. } => Some(CurrencyAmount::new(
+            &btc_currency_id(),
+            lightning::msats_to_btc(*amount_msat),
+        )),
+        Payment::Ach {
+            currency_id,
+            amount,
+            ..
+        }
+        | Payment::Check {
+            currency_id,
+            amount,
+            ..
+        }
+        | Payment::Cash {
+            currency_id,
+            amount,
+            ..
+        } => Some(CurrencyAmount::new(currency_id, *amount)),
+    }
+}
+
+// on-chain amounts are whole satoshis, lightning amounts may use the currency's finest unit,
+// payments, write offs, late fees and discounts use the scale of their invoice
+fn entry_scale(ledger: &Ledger, transaction: &Transaction, currency: &Currency) -> CurrencyScale {
+    match &transaction.transaction_type {
+        TransactionType::InvoicePayment { transaction_id }
+        | TransactionType::WriteOff { transaction_id }
+        | TransactionType::LateFee { transaction_id }
+        | TransactionType::Discount { transaction_id } => ledger
+            .get_transaction(transaction_id)
+            .filter(|invoice| {
+                matches!(
+                    invoice.transaction_type,
+                    TransactionType::Invoice { .. } | TransactionType::Bill { .. }
+                )
+            })
+            .map_or(currency.scale, |invoice| {
+                entry_scale(ledger, &invoice, currency)
+            }),
+        TransactionType::Invoice {
+            payment_method: PaymentMethod::Lightning { .. },
+            ..
+        }
+        | TransactionType::Bill {
+            payment_method: PaymentMethod::Lightning { .. },
+            ..
+        } => currency.finest_scale(),
+        TransactionType::OnChain { .. }
+        | TransactionType::Invoice {
+            payment_method: PaymentMethod::Bitcoin { .. },
+            ..
+        }
+        | TransactionType::Bill {
+            payment_method: PaymentMethod::Bitcoin { .. },
+            ..
+        } => currency
+            .get_unit("sat")
+            .map_or(currency.scale, |sat| sat.scale.min(currency.scale)),
+        _ => currency.scale,
+    }
+}
+
+// bitcoin account and derivation index of an invoice's receive address
+fn invoice_address_index(transaction: &Transaction) -> Option<(AccountId, u32)> {
+    match &transaction.transaction_type {
+        TransactionType::Invoice {
+            payment_method:
+                PaymentMethod::Bitcoin {
+                    account_id: Some(account_id),
+                    derivation_index: Some(index),
+                    ..
+                },
+            ..
+        } => Some((*account_id, *index)),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use crate::journal::{Account, AccountCategory, AccountType, BalanceSheetCategory, Contact, ContactType, JournalEntry, Organization, test_entries};
@@ -417,6 +1644,18 @@ pub(crate) mod test {
     use std::sync::Once;
     use rusty_ulid::Ulid;
     use crate::journal::Action::{AddAccount, AddOrganization};
+    use crate::journal::Action::{
+        AddCurrency, AddExchangeRate, AddTransaction, ArchiveAccount, ClosePeriod,
+        ReverseTransaction, UpdateAccount, UpdateContact, VoidTransaction,
+    };
+    use crate::journal::{
+        Currency, CurrencyAmount, CurrencyUnit, ExchangeRate, PaymentMethod, PaymentTerms,
+        PeriodClose, Transaction, TransactionType, TransactionVoid, UnitAmount,
+    };
+    use crate::ledger::Error;
+    use crate::lightning::test::TEST_INVOICE;
+    use rust_decimal::Decimal;
+    use time::macros::datetime;
 
     static INIT: Once = Once::new();
 
@@ -614,7 +1853,7 @@ pub(crate) mod test {
             contact: organization_contact,
             organization: organization
         })).expect("Add organization");
-        let test_account = Account {
+        let mut test_account = Account {
             id: Ulid::generate(),
             parent_id: Some(Ulid::generate()),
             number: 10,
@@ -625,19 +1864,758 @@ pub(crate) mod test {
             account_category: AccountCategory::BalanceSheet(BalanceSheetCategory::Equity),
         };
 
-        let result = organization_ledgers.add_journal_entry(JournalEntry {
-            id: Ulid::generate(),
-            version: 0,
-            organization_id,
-            action: AddAccount {
-                account: test_account,
+        let mut add_account = |account: &Account| {
+            organization_ledgers.add_journal_entry(JournalEntry {
+                id: Ulid::generate(),
+                version: 0,
+                organization_id,
+                action: AddAccount {
+                    account: account.clone(),
+                },
+            })
+        };
+
+        let result = add_account(&test_account);
+        assert!(matches!(result, Err(Error::MissingAccount(_))));
+
+        let equity_account = Account::new(
+            None,
+            300,
+            "Equity".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Equity),
+        );
+        add_account(&equity_account).expect("equity account");
+
+        test_account.parent_id = Some(equity_account.id);
+        let result = add_account(&test_account);
+        assert!(matches!(result, Err(Error::MissingContact(_))));
+
+        test_account.account_type = AccountType::LedgerAccount;
+        test_account.account_category = AccountCategory::BalanceSheet(BalanceSheetCategory::Asset);
+        let result = add_account(&test_account);
+        assert!(matches!(result, Err(Error::AccountCategoryMismatch(_))));
+
+        test_account.account_category = AccountCategory::BalanceSheet(BalanceSheetCategory::Equity);
+        test_account.parent_id = Some(test_account.id);
+        let result = add_account(&test_account);
+        assert!(matches!(result, Err(Error::AccountParentCycle(_))));
+
+        let mut duplicate_account = equity_account.clone();
+        duplicate_account.id = Ulid::generate();
+        let result = add_account(&duplicate_account);
+        assert!(matches!(result, Err(Error::AccountNumberExists(300))));
+
+        test_account.parent_id = Some(equity_account.id);
+        add_account(&test_account).expect("valid account");
+    }
+
+    #[test]
+    fn test_invalid_transaction() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let debit_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let credit_account_id = test_entries.accounts.get(5).expect("owner 1").id;
+
+        let mut add_transaction = |transaction: &Transaction, ledger_entries: Vec<LedgerEntry>| {
+            organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction: transaction.clone(),
+                    ledger_entries,
+                },
+            ))
+        };
+        let transaction = Transaction::new(
+            datetime!(2022-03-03 09:00 UTC),
+            "Owner's additional funding".to_string(),
+            TransactionType::LedgerAdjustment,
+        );
+        let entry = |entry_type: EntryType, account_id: &Ulid, amount: Decimal| {
+            LedgerEntry::new(
+                &transaction.id,
+                entry_type,
+                account_id,
+                CurrencyAmount::new(&usd_id, amount),
+                None,
+            )
+        };
+
+        let result = add_transaction(&transaction, vec![]);
+        assert!(matches!(result, Err(Error::MissingLedgerEntries(_))));
+
+        let result = add_transaction(
+            &transaction,
+            vec![
+                entry(EntryType::Debit, &debit_account_id, Decimal::new(10_00, 2)),
+                entry(EntryType::Credit, &credit_account_id, Decimal::new(9_00, 2)),
+            ],
+        );
+        assert!(matches!(
+            result,
+            Err(Error::UnbalancedLedgerEntries(_, 840))
+        ));
+
+        let result = add_transaction(
+            &transaction,
+            vec![
+                entry(EntryType::Debit, &debit_account_id, Decimal::new(10_00, 2)),
+                entry(EntryType::Credit, &Ulid::generate(), Decimal::new(10_00, 2)),
+            ],
+        );
+        assert!(matches!(result, Err(Error::MissingAccount(_))));
+
+        let mut mismatched_entry = entry(
+            EntryType::Credit,
+            &credit_account_id,
+            Decimal::new(10_00, 2),
+        );
+        mismatched_entry.transaction_id = Ulid::generate();
+        let result = add_transaction(
+            &transaction,
+            vec![
+                entry(EntryType::Debit, &debit_account_id, Decimal::new(10_00, 2)),
+                mismatched_entry,
+            ],
+        );
+        assert!(matches!(result, Err(Error::TransactionIdMismatch(_))));
+
+        let result = add_transaction(
+            &transaction,
+            vec![
+                entry(EntryType::Debit, &debit_account_id, Decimal::new(10_001, 3)),
+                entry(
+                    EntryType::Credit,
+                    &credit_account_id,
+                    Decimal::new(10_001, 3),
+                ),
+            ],
+        );
+        assert!(matches!(result, Err(Error::CurrencyScaleExceeded(840))));
+
+        for amount in [Decimal::ZERO, Decimal::new(-10_00, 2)] {
+            let result = add_transaction(
+                &transaction,
+                vec![
+                    entry(EntryType::Debit, &debit_account_id, amount),
+                    entry(EntryType::Credit, &credit_account_id, amount),
+                ],
+            );
+            assert!(matches!(result, Err(Error::InvalidEntryAmount(_))));
+        }
+
+        let mut lightning_transaction = transaction.clone();
+        lightning_transaction.transaction_type = TransactionType::Invoice {
+            payment_method: PaymentMethod::Lightning {
+                invoice: "lnbc2500u1invalid".to_string(),
             },
+            payment_terms: PaymentTerms::ImmediatePayment,
+            payments: vec![],
+            line_items: vec![],
+        };
+        let result = add_transaction(
+            &lightning_transaction,
+            vec![
+                entry(EntryType::Debit, &debit_account_id, Decimal::new(10_00, 2)),
+                entry(
+                    EntryType::Credit,
+                    &credit_account_id,
+                    Decimal::new(10_00, 2),
+                ),
+            ],
+        );
+        assert!(matches!(result, Err(Error::InvalidPayment(_))));
+
+        let result = add_transaction(
+            &transaction,
+            vec![
+                entry(EntryType::Debit, &debit_account_id, Decimal::new(10_000, 3)),
+                entry(
+                    EntryType::Credit,
+                    &credit_account_id,
+                    Decimal::new(10_00, 2),
+                ),
+            ],
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_exchange_rate() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+
+        let mut add_exchange_rate = |exchange_rate: ExchangeRate| {
+            organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddExchangeRate { exchange_rate },
+            ))
+        };
+        let exchange_rate = ExchangeRate::new(
+            &btc_id,
+            &usd_id,
+            Decimal::new(40_000, 0),
+            datetime!(2022-01-01 00:00 UTC),
+            "test".to_string(),
+        );
+        add_exchange_rate(exchange_rate.clone()).expect("add exchange rate");
+        let result = add_exchange_rate(exchange_rate.clone());
+        assert!(matches!(result, Err(Error::ExchangeRateExists(2009, 840))));
+        let result = add_exchange_rate(ExchangeRate {
+            rate: Decimal::ZERO,
+            ..exchange_rate.clone()
         });
+        assert!(matches!(result, Err(Error::InvalidExchangeRate(2009, 840))));
+        add_exchange_rate(ExchangeRate::new(
+            &usd_id,
+            &btc_id,
+            Decimal::new(2, 5),
+            datetime!(2022-02-01 00:00 UTC),
+            "test".to_string(),
+        ))
+        .expect("add inverse exchange rate");
 
-        if let Err(e) = result {
-            debug!("Expected validation error: {:?}", e);
-        } else {
-            debug!("Expected ok result");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        assert_eq!(
+            ledger.exchange_rate(&btc_id, &usd_id, &datetime!(2021-12-31 00:00 UTC)),
+            None
+        );
+        assert_eq!(
+            ledger.exchange_rate(&btc_id, &usd_id, &datetime!(2022-01-15 00:00 UTC)),
+            Some(Decimal::new(40_000, 0))
+        );
+        assert_eq!(
+            ledger.exchange_rate(&btc_id, &usd_id, &datetime!(2022-02-15 00:00 UTC)),
+            Some(Decimal::new(50_000, 0))
+        );
+        let converted = ledger
+            .convert(
+                &CurrencyAmount::new(&btc_id, Decimal::new(12_345, 8)),
+                &usd_id,
+                &datetime!(2022-01-15 00:00 UTC),
+            )
+            .expect("converted");
+        assert_eq!(converted.amount, Decimal::new(4_94, 2));
+        let result = ledger.convert(
+            &CurrencyAmount::new(&btc_id, Decimal::ONE),
+            &usd_id,
+            &datetime!(2021-12-31 00:00 UTC),
+        );
+        assert!(matches!(result, Err(Error::MissingExchangeRate(2009, 840))));
+    }
+
+    #[test]
+    fn test_reverse_transaction() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let (income_tx, _) = test_entries.transactions.get(1).expect("income tx");
+        let reversal = Transaction::new_reversal(
+            datetime!(2022-03-03 09:00 UTC),
+            "Reverse consulting income".to_string(),
+            &income_tx.id,
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                ReverseTransaction {
+                    transaction: reversal.clone(),
+                },
+            ))
+            .expect("reverse transaction");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        assert_eq!(ledger.get_reversal(&income_tx.id), Some(Arc::new(reversal)));
+        assert!(ledger.get_transaction(&income_tx.id).is_some());
+        let bank_account_id = test_entries.accounts.get(6).expect("bank").id;
+        let bank_entries = ledger
+            .get_account_entries(&bank_account_id)
+            .expect("entries");
+        assert_eq!(bank_entries.len(), 3);
+        let reversal_entry = bank_entries.get(2).expect("reversal entry");
+        assert_eq!(reversal_entry.entry_type, EntryType::Credit);
+        assert_eq!(
+            reversal_entry.currency_amount.amount,
+            Decimal::new(8_000_00, 2)
+        );
+
+        let double_reversal = Transaction::new_reversal(
+            datetime!(2022-03-04 09:00 UTC),
+            "Reverse consulting income again".to_string(),
+            &income_tx.id,
+        );
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            ReverseTransaction {
+                transaction: double_reversal,
+            },
+        ));
+        assert!(matches!(result, Err(Error::TransactionReversed(_))));
+
+        let void = TransactionVoid::new(
+            &income_tx.id,
+            datetime!(2022-03-04 09:00 UTC),
+            "Void consulting income".to_string(),
+        );
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            VoidTransaction { void },
+        ));
+        assert!(matches!(result, Err(Error::TransactionReversed(_))));
+    }
+
+    #[test]
+    fn test_void_transaction() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let (income_tx, _) = test_entries.transactions.get(1).expect("income tx");
+        let void = TransactionVoid::new(
+            &income_tx.id,
+            datetime!(2022-03-03 09:00 UTC),
+            "Void consulting income".to_string(),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                VoidTransaction { void: void.clone() },
+            ))
+            .expect("void transaction");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        assert_eq!(ledger.get_void(&income_tx.id), Some(Arc::new(void.clone())));
+        assert!(ledger.get_transaction(&income_tx.id).is_some());
+        let bank_account_id = test_entries.accounts.get(6).expect("bank").id;
+        let bank_entries = ledger.get_account_entries_period(
+            &bank_account_id,
+            None,
+            &datetime!(2022-12-31 0:00 UTC),
+        );
+        assert_eq!(bank_entries.len(), 1);
+
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            VoidTransaction { void },
+        ));
+        assert!(matches!(result, Err(Error::TransactionVoided(_))));
+    }
+
+    #[test]
+    fn test_update_account() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let assets_account = test_entries.accounts.get(0).expect("assets");
+        let cash_account = Account::new(
+            Some(&assets_account.id),
+            200,
+            "Cash".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: cash_account.clone(),
+                },
+            ))
+            .expect("add account");
+
+        let mut bank_account = test_entries.accounts.get(6).expect("bank").clone();
+        bank_account.description = "Bank Operating".to_string();
+        bank_account.parent_id = Some(cash_account.id);
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                UpdateAccount {
+                    account: bank_account.clone(),
+                },
+            ))
+            .expect("update account");
+
+        let mut moved_cash_account = cash_account.clone();
+        moved_cash_account.parent_id = Some(bank_account.id);
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            UpdateAccount {
+                account: moved_cash_account,
+            },
+        ));
+        assert!(matches!(result, Err(Error::AccountParentCycle(_))));
+
+        let mut owner = test_entries.contacts.get(0).expect("owner").clone();
+        owner.address = Some("1 Main St".to_string());
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                UpdateContact {
+                    contact: owner.clone(),
+                },
+            ))
+            .expect("update contact");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let history = ledger.get_account_history(&bank_account.id);
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history.get(0).expect("previous").description,
+            "Bank Checking"
+        );
+        assert_eq!(history.get(1).expect("current").as_ref(), &bank_account);
+        assert_eq!(
+            ledger.full_number(&bank_account).expect("full number"),
+            vec![100, 200, 100]
+        );
+        assert_eq!(ledger.get_contact_history(&owner.id).len(), 2);
+        assert_eq!(ledger.get_contact(&owner.id), Some(Arc::new(owner)));
+    }
+
+    #[test]
+    fn test_archive_account() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let bank_account_id = test_entries.accounts.get(6).expect("bank").id;
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                ArchiveAccount {
+                    account_id: bank_account_id,
+                    datetime: datetime!(2022-03-01 00:00 UTC),
+                },
+            ))
+            .expect("archive account");
+
+        let (_, ledger_entries) = test_entries.transactions.get(1).expect("income tx");
+        let transaction = Transaction::new(
+            datetime!(2022-03-03 09:00 UTC),
+            "Consulting income".to_string(),
+            TransactionType::LedgerAdjustment,
+        );
+        let ledger_entries = ledger_entries
+            .iter()
+            .map(|entry| LedgerEntry {
+                transaction_id: transaction.id,
+                ..entry.clone()
+            })
+            .collect();
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            AddTransaction {
+                transaction,
+                ledger_entries,
+            },
+        ));
+        assert!(matches!(result, Err(Error::AccountArchived(_))));
+
+        // entries dated before the account was archived are allowed
+        let (_, ledger_entries) = test_entries.transactions.get(1).expect("income tx");
+        let transaction = Transaction::new(
+            datetime!(2022-02-28 09:00 UTC),
+            "Consulting income".to_string(),
+            TransactionType::LedgerAdjustment,
+        );
+        let ledger_entries = ledger_entries
+            .iter()
+            .map(|entry| LedgerEntry {
+                transaction_id: transaction.id,
+                ..entry.clone()
+            })
+            .collect();
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+            .expect("add transaction before archive");
+    }
+
+    #[test]
+    fn test_close_period() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let equity = test_entries.accounts.get(2).expect("equity");
+        let retained_earnings = Account::new(
+            Some(&equity.id),
+            200,
+            "Retained Earnings".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Equity),
+        );
+        let retained_earnings_id = retained_earnings.id;
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: retained_earnings,
+                },
+            ))
+            .expect("add retained earnings account");
+        let consulting_income_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let period_close =
+            PeriodClose::new_fiscal_year(&test_entries.organization, 2022, &retained_earnings_id);
+        assert_eq!(period_close.start_date_time, datetime!(2022-01-01 0:00 UTC));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                ClosePeriod {
+                    period_close: period_close.clone(),
+                },
+            ))
+            .expect("close period");
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let closing_entries = ledger
+            .get_transaction_entries(&period_close.transaction_id)
+            .expect("closing entries");
+        assert_eq!(closing_entries.len(), 2);
+        let income_entry = closing_entries
+            .iter()
+            .find(|entry| entry.account_id == consulting_income_id)
+            .expect("income closing entry");
+        assert_eq!(income_entry.entry_type, EntryType::Debit);
+        assert_eq!(
+            income_entry.currency_amount.amount,
+            Decimal::new(8_000_00, 2)
+        );
+        let earnings_entry = closing_entries
+            .iter()
+            .find(|entry| entry.account_id == retained_earnings_id)
+            .expect("retained earnings closing entry");
+        assert_eq!(earnings_entry.entry_type, EntryType::Credit);
+        assert_eq!(
+            earnings_entry.currency_amount.amount,
+            Decimal::new(8_000_00, 2)
+        );
+
+        let (_, ledger_entries) = test_entries.transactions.get(1).expect("income tx");
+        let mut add_transaction = |datetime| {
+            let transaction = Transaction::new(
+                datetime,
+                "Consulting income".to_string(),
+                TransactionType::LedgerAdjustment,
+            );
+            let ledger_entries = ledger_entries
+                .iter()
+                .map(|entry| LedgerEntry {
+                    transaction_id: transaction.id,
+                    ..entry.clone()
+                })
+                .collect();
+            organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+        };
+        let result = add_transaction(datetime!(2022-06-01 09:00 UTC));
+        assert!(matches!(result, Err(Error::PeriodLocked(_))));
+        let result = add_transaction(datetime!(2023-01-01 09:00 UTC));
+        assert!(result.is_ok());
+
+        let overlapping_close = PeriodClose::new(
+            datetime!(2022-12-01 0:00 UTC),
+            datetime!(2023-01-31 0:00 UTC),
+            &retained_earnings_id,
+        );
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            ClosePeriod {
+                period_close: overlapping_close,
+            },
+        ));
+        assert!(matches!(result, Err(Error::PeriodLocked(_))));
+
+        let mut close_fiscal_year = |year| {
+            let period_close = PeriodClose::new_fiscal_year(
+                &test_entries.organization,
+                year,
+                &retained_earnings_id,
+            );
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    ClosePeriod {
+                        period_close: period_close.clone(),
+                    },
+                ))
+                .map(|_| period_close)
+        };
+        let result = close_fiscal_year(2021);
+        assert!(matches!(result, Err(Error::PeriodOutOfOrder(_))));
+        let result = close_fiscal_year(2024);
+        assert!(matches!(result, Err(Error::PeriodGap(_))));
+        let period_close = close_fiscal_year(2023).expect("close next period");
+
+        // only income from the 2023 period is closed, not the 2022 income again
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let closing_entries = ledger
+            .get_transaction_entries(&period_close.transaction_id)
+            .expect("closing entries");
+        let earnings_entry = closing_entries
+            .iter()
+            .find(|entry| entry.account_id == retained_earnings_id)
+            .expect("retained earnings closing entry");
+        assert_eq!(
+            earnings_entry.currency_amount.amount,
+            Decimal::new(8_000_00, 2)
+        );
+    }
+
+    #[test]
+    fn test_currency_units() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let debit_account_id = test_entries.accounts.get(6).expect("bank checking").id;
+        let credit_account_id = test_entries.accounts.get(5).expect("owner 1").id;
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let sats = UnitAmount {
+            currency_id: btc_id,
+            amount: Decimal::new(12_345, 0),
+            unit: Some("sat".to_string()),
+        };
+        assert_eq!(
+            ledger.unit_amount(&sats).expect("sats"),
+            CurrencyAmount::new(&btc_id, Decimal::new(12_345, 8))
+        );
+        let bits = UnitAmount {
+            unit: Some("bit".to_string()),
+            ..sats
+        };
+        assert!(matches!(
+            ledger.unit_amount(&bits),
+            Err(Error::MissingCurrencyUnit(2009, _))
+        ));
+        for scale in [1, 29] {
+            let currency = Currency {
+                id: 978,
+                code: "EUR".to_string(),
+                scale: 2,
+                name: "Euro".to_string(),
+                units: vec![CurrencyUnit::new(
+                    "x".to_string(),
+                    "Test".to_string(),
+                    scale,
+                )],
+            };
+            let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddCurrency { currency },
+            ));
+            assert!(matches!(result, Err(Error::InvalidCurrencyUnit(978, _))));
         }
+
+        let mut add_transaction = |transaction_type: TransactionType, amount: Decimal| {
+            let transaction = Transaction::new(
+                datetime!(2022-03-03 09:00 UTC),
+                "Bitcoin funding".to_string(),
+                transaction_type,
+            );
+            let entry = |entry_type: EntryType, account_id: &Ulid| {
+                LedgerEntry::new(
+                    &transaction.id,
+                    entry_type,
+                    account_id,
+                    CurrencyAmount::new(&btc_id, amount),
+                    None,
+                )
+            };
+            let ledger_entries = vec![
+                entry(EntryType::Debit, &debit_account_id),
+                entry(EntryType::Credit, &credit_account_id),
+            ];
+            organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+        };
+
+        // on-chain amounts are whole sats, lightning amounts may be msats
+        let msats = Decimal::new(1_500, 11);
+        let on_chain = TransactionType::OnChain {
+            txid: "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16".to_string(),
+        };
+        let result = add_transaction(on_chain, msats);
+        assert!(matches!(result, Err(Error::CurrencyScaleExceeded(2009))));
+        let lightning = TransactionType::Invoice {
+            payment_method: PaymentMethod::Lightning {
+                invoice: TEST_INVOICE.to_string(),
+            },
+            payment_terms: PaymentTerms::ImmediatePayment,
+            payments: vec![],
+            line_items: vec![],
+        };
+        add_transaction(lightning, msats).expect("lightning transaction");
     }
 }
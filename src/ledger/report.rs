@@ -1,5 +1,14 @@
-use crate::journal::{Account, AccountId, CurrencyAmount, CurrencyId, EntryType, LedgerEntry};
-use crate::ledger::Ledger;
+use crate::journal::money::{Money, RoundingMode};
+use crate::journal::BalanceSheetCategory::{Asset, Equity, Liability};
+use crate::journal::IncomeStatementCategory::{
+    NonOperatingExpense, NonOperatingRevenue, OperatingExpense, OperatingRevenue,
+};
+use crate::journal::{
+    Account, AccountCategory, AccountId, AccountNumber, ContactId, Currency, CurrencyAmount,
+    CurrencyId, EntryType, LedgerEntry, Organization, Transaction, TransactionType,
+};
+use crate::ledger::invoice::{BalanceType, InvoiceBalance};
+use crate::ledger::{Error, Ledger};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -9,25 +18,547 @@ use time::OffsetDateTime;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Report {
+    pub start_date_time: Option<OffsetDateTime>,
     pub date_time: OffsetDateTime,
     pub account_ids: Vec<AccountId>,
     pub account_totals: Vec<AccountTotals>,
+    pub reporting_currency_id: Option<CurrencyId>,
+    pub converted_balances: Vec<CurrencyAmount>,
 }
 
 impl Report {
+    // totals of all entries at or before date time, ie. for a balance sheet
     pub fn new(ledger: &Ledger, date_time: OffsetDateTime, account_ids: Vec<AccountId>) -> Self {
+        Report::new_period(ledger, None, date_time, account_ids)
+    }
+
+    // totals of entries from start date time to date time, ie. for an income statement
+    pub fn new_period(
+        ledger: &Ledger,
+        start_date_time: Option<OffsetDateTime>,
+        date_time: OffsetDateTime,
+        account_ids: Vec<AccountId>,
+    ) -> Self {
         let accounts = account_ids
             .iter()
             .map(|id| ledger.get_account(&id).expect("account"));
-        let account_totals: Vec<AccountTotals> =
-            accounts.map(|a| AccountTotals::new(ledger, a)).collect();
+        let account_totals: Vec<AccountTotals> = accounts
+            .map(|a| AccountTotals::new_period(ledger, a, start_date_time, date_time))
+            .collect();
 
         Report {
+            start_date_time,
             date_time,
             account_ids,
             account_totals,
+            reporting_currency_id: None,
+            converted_balances: Vec::new(),
+        }
+    }
+
+    // report with each account's balances converted to the reporting currency at date time, or
+    // to the organization's functional currency if no reporting currency is given
+    pub fn new_converted(
+        ledger: &Ledger,
+        organization: &Organization,
+        start_date_time: Option<OffsetDateTime>,
+        date_time: OffsetDateTime,
+        account_ids: Vec<AccountId>,
+        reporting_currency_id: Option<&CurrencyId>,
+    ) -> Result<Self, Error> {
+        let reporting_currency_id =
+            reporting_currency_id.unwrap_or(&organization.functional_currency_id);
+        let report = Report::new_period(ledger, start_date_time, date_time, account_ids);
+        let converted_balances = report
+            .account_totals
+            .iter()
+            .map(|totals| totals.converted_balance(ledger, reporting_currency_id, &date_time))
+            .collect::<Result<Vec<CurrencyAmount>, Error>>()?;
+        Ok(Report {
+            reporting_currency_id: Some(*reporting_currency_id),
+            converted_balances,
+            ..report
+        })
+    }
+}
+
+/// Root accounts of one account category with their combined balances, income statement
+/// accounts exclude period close entries
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ReportSection {
+    pub account_totals: Vec<AccountTotals>,
+    pub subtotals: Vec<CurrencyAmount>,
+}
+
+impl ReportSection {
+    pub fn new(
+        ledger: &Ledger,
+        category: AccountCategory,
+        start_date_time: Option<OffsetDateTime>,
+        date_time: OffsetDateTime,
+    ) -> Self {
+        let account_totals: Vec<AccountTotals> = ledger
+            .get_root_accounts(category.clone())
+            .iter()
+            .map(|id| ledger.get_account(id).expect("account"))
+            .map(|a| match category {
+                AccountCategory::BalanceSheet(_) => {
+                    AccountTotals::new_period(ledger, a, start_date_time, date_time)
+                }
+                AccountCategory::IncomeStatement(_) => {
+                    AccountTotals::new_period_before_close(ledger, a, start_date_time, date_time)
+                }
+            })
+            .collect();
+        let balances: Vec<&CurrencyAmount> = account_totals
+            .iter()
+            .flat_map(|totals| totals.balances.iter())
+            .collect();
+        let subtotals = net_amounts(balances, vec![]);
+        ReportSection {
+            account_totals,
+            subtotals,
         }
     }
+
+    pub fn subtotal(&self, currency_id: &CurrencyId) -> Decimal {
+        amount(&self.subtotals, currency_id)
+    }
+}
+
+/// Revenue and expenses for a period and the resulting net income
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct IncomeStatement {
+    pub start_date_time: Option<OffsetDateTime>,
+    pub date_time: OffsetDateTime,
+    pub operating_revenue: ReportSection,
+    pub operating_expenses: ReportSection,
+    pub non_operating_revenue: ReportSection,
+    pub non_operating_expenses: ReportSection,
+    pub net_income: Vec<CurrencyAmount>,
+}
+
+impl IncomeStatement {
+    pub fn new(
+        ledger: &Ledger,
+        start_date_time: Option<OffsetDateTime>,
+        date_time: OffsetDateTime,
+    ) -> Self {
+        let [operating_revenue, operating_expenses, non_operating_revenue, non_operating_expenses] =
+            [
+                OperatingRevenue,
+                OperatingExpense,
+                NonOperatingRevenue,
+                NonOperatingExpense,
+            ]
+            .map(|category| {
+                ReportSection::new(
+                    ledger,
+                    AccountCategory::IncomeStatement(category),
+                    start_date_time,
+                    date_time,
+                )
+            });
+        let revenue = [&operating_revenue, &non_operating_revenue]
+            .iter()
+            .flat_map(|section| section.subtotals.iter())
+            .collect();
+        let expenses = [&operating_expenses, &non_operating_expenses]
+            .iter()
+            .flat_map(|section| section.subtotals.iter())
+            .collect();
+        let net_income = net_amounts(revenue, expenses);
+        IncomeStatement {
+            start_date_time,
+            date_time,
+            operating_revenue,
+            operating_expenses,
+            non_operating_revenue,
+            non_operating_expenses,
+            net_income,
+        }
+    }
+
+    pub fn net_income(&self, currency_id: &CurrencyId) -> Decimal {
+        amount(&self.net_income, currency_id)
+    }
+}
+
+/// Assets, liabilities and equity, including earnings not yet closed to equity, at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct BalanceSheet {
+    pub date_time: OffsetDateTime,
+    pub assets: ReportSection,
+    pub liabilities: ReportSection,
+    pub equity: ReportSection,
+    pub current_earnings: Vec<CurrencyAmount>,
+    pub total_equity: Vec<CurrencyAmount>,
+}
+
+impl BalanceSheet {
+    pub fn new(ledger: &Ledger, date_time: OffsetDateTime) -> Self {
+        let [assets, liabilities, equity] = [Asset, Liability, Equity].map(|category| {
+            ReportSection::new(
+                ledger,
+                AccountCategory::BalanceSheet(category),
+                None,
+                date_time,
+            )
+        });
+        let current_period_start = ledger.current_period_start(&date_time);
+        let current_earnings =
+            IncomeStatement::new(ledger, current_period_start, date_time).net_income;
+        let total_equity = net_amounts(
+            equity
+                .subtotals
+                .iter()
+                .chain(current_earnings.iter())
+                .collect(),
+            vec![],
+        );
+        BalanceSheet {
+            date_time,
+            assets,
+            liabilities,
+            equity,
+            current_earnings,
+            total_equity,
+        }
+    }
+
+    pub fn total_equity(&self, currency_id: &CurrencyId) -> Decimal {
+        amount(&self.total_equity, currency_id)
+    }
+
+    // currencies where assets do not equal liabilities plus equity
+    pub fn unbalanced_currencies(&self) -> Vec<CurrencyId> {
+        let liabilities_and_equity = self
+            .liabilities
+            .subtotals
+            .iter()
+            .chain(self.total_equity.iter())
+            .collect();
+        net_amounts(
+            self.assets.subtotals.iter().collect(),
+            liabilities_and_equity,
+        )
+        .iter()
+        .filter(|difference| !difference.amount.is_zero())
+        .map(|difference| difference.currency_id)
+        .collect()
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.unbalanced_currencies().is_empty()
+    }
+}
+
+/// Debit and credit totals of the entries posted directly to an account
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TrialBalanceLine {
+    pub account: Arc<Account>,
+    pub full_number: Vec<AccountNumber>,
+    pub debit_totals: Vec<CurrencyAmount>,
+    pub credit_totals: Vec<CurrencyAmount>,
+}
+
+/// Debit and credit totals of every account at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct TrialBalance {
+    pub date_time: OffsetDateTime,
+    pub lines: Vec<TrialBalanceLine>,
+    pub debit_totals: Vec<CurrencyAmount>,
+    pub credit_totals: Vec<CurrencyAmount>,
+    pub unbalanced_currencies: Vec<CurrencyId>,
+}
+
+impl TrialBalance {
+    pub fn new(ledger: &Ledger, date_time: OffsetDateTime) -> Self {
+        let mut lines: Vec<TrialBalanceLine> = ledger
+            .accounts()
+            .into_iter()
+            .map(|account| {
+                let full_number = ledger.full_number(&account).expect("full number");
+                let account_entries =
+                    ledger.get_account_entries_period(&account.id, None, &date_time);
+                let [debit_totals, credit_totals] = AccountTotals::entry_totals(&account_entries);
+                TrialBalanceLine {
+                    account,
+                    full_number,
+                    debit_totals,
+                    credit_totals,
+                }
+            })
+            .collect();
+        lines.sort_by(|a, b| a.full_number.cmp(&b.full_number));
+
+        let debit_totals = net_amounts(
+            lines
+                .iter()
+                .flat_map(|line| line.debit_totals.iter())
+                .collect(),
+            vec![],
+        );
+        let credit_totals = net_amounts(
+            lines
+                .iter()
+                .flat_map(|line| line.credit_totals.iter())
+                .collect(),
+            vec![],
+        );
+        let unbalanced_currencies = net_amounts(
+            debit_totals.iter().collect(),
+            credit_totals.iter().collect(),
+        )
+        .iter()
+        .filter(|difference| !difference.amount.is_zero())
+        .map(|difference| difference.currency_id)
+        .collect();
+
+        TrialBalance {
+            date_time,
+            lines,
+            debit_totals,
+            credit_totals,
+            unbalanced_currencies,
+        }
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.unbalanced_currencies.is_empty()
+    }
+}
+
+/// Ledger entry of a register with its transaction and the balance after the entry
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct RegisterLine {
+    pub transaction: Arc<Transaction>,
+    pub entry: Arc<LedgerEntry>,
+    pub counter_account_ids: Vec<AccountId>,
+    pub balances: Vec<CurrencyAmount>,
+}
+
+/// Ledger entries of an account, and optionally its descendants, with running balances
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct AccountRegister {
+    pub account: Arc<Account>,
+    pub include_descendants: bool,
+    pub start_date_time: Option<OffsetDateTime>,
+    pub date_time: OffsetDateTime,
+    pub opening_balances: Vec<CurrencyAmount>,
+    pub lines: Vec<RegisterLine>,
+    pub closing_balances: Vec<CurrencyAmount>,
+}
+
+impl AccountRegister {
+    pub fn new(
+        ledger: &Ledger,
+        account: Arc<Account>,
+        include_descendants: bool,
+        start_date_time: Option<OffsetDateTime>,
+        date_time: OffsetDateTime,
+    ) -> Self {
+        let mut account_ids = vec![account.id];
+        if include_descendants {
+            account_ids.append(&mut ledger.descendant_ids(&account.id));
+        }
+        let mut entries: Vec<(Arc<Transaction>, Arc<LedgerEntry>)> = account_ids
+            .iter()
+            .flat_map(|account_id| ledger.get_account_entries_period(account_id, None, &date_time))
+            .map(|entry| {
+                let transaction = ledger
+                    .get_transaction(&entry.transaction_id)
+                    .expect("transaction");
+                (transaction, entry)
+            })
+            .collect();
+        entries.sort_by_key(|(a, _)| (a.datetime, a.id));
+
+        let normal_entry_type = account.account_category.normal_entry_type();
+        let mut balances: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+        let mut opening_balances = Vec::new();
+        let mut lines = Vec::new();
+        for (transaction, entry) in entries {
+            let in_period = start_date_time.is_none_or(|start| transaction.datetime >= start);
+            if in_period && lines.is_empty() {
+                opening_balances = currency_amounts(&balances);
+            }
+            let currency_amount = &entry.currency_amount;
+            let balance = balances.entry(currency_amount.currency_id).or_default();
+            if entry.entry_type == normal_entry_type {
+                *balance += currency_amount.amount;
+            } else {
+                *balance -= currency_amount.amount;
+            }
+            if in_period {
+                let mut counter_account_ids: Vec<AccountId> = ledger
+                    .get_transaction_entries(&transaction.id)
+                    .iter()
+                    .flatten()
+                    .map(|e| e.account_id)
+                    .filter(|account_id| !account_ids.contains(account_id))
+                    .collect();
+                counter_account_ids.sort();
+                counter_account_ids.dedup();
+                lines.push(RegisterLine {
+                    transaction,
+                    entry,
+                    counter_account_ids,
+                    balances: currency_amounts(&balances),
+                });
+            }
+        }
+        let closing_balances = currency_amounts(&balances);
+        if lines.is_empty() {
+            opening_balances = closing_balances.clone();
+        }
+
+        AccountRegister {
+            account,
+            include_descendants,
+            start_date_time,
+            date_time,
+            opening_balances,
+            lines,
+            closing_balances,
+        }
+    }
+}
+
+/// Outstanding invoice amounts by days past the payment terms due date
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct AgingBuckets {
+    pub current: Vec<CurrencyAmount>,
+    pub days_1_30: Vec<CurrencyAmount>,
+    pub days_31_60: Vec<CurrencyAmount>,
+    pub days_61_90: Vec<CurrencyAmount>,
+    pub days_over_90: Vec<CurrencyAmount>,
+    pub totals: Vec<CurrencyAmount>,
+}
+
+impl AgingBuckets {
+    pub fn new(invoices: &[InvoiceBalance], date_time: &OffsetDateTime) -> Self {
+        let mut buckets: [BTreeMap<CurrencyId, Decimal>; 5] = Default::default();
+        for invoice in invoices {
+            let days_past_due = (date_time.date() - invoice.due_date_time.date()).whole_days();
+            let bucket = match days_past_due {
+                i64::MIN..=0 => 0,
+                1..=30 => 1,
+                31..=60 => 2,
+                61..=90 => 3,
+                _ => 4,
+            };
+            *buckets[bucket].entry(invoice.currency_id).or_default() += invoice.outstanding;
+        }
+        let [current, days_1_30, days_31_60, days_61_90, days_over_90] =
+            buckets.map(|bucket| currency_amounts(&bucket));
+        let totals = net_amounts(
+            [
+                &current,
+                &days_1_30,
+                &days_31_60,
+                &days_61_90,
+                &days_over_90,
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            vec![],
+        );
+        AgingBuckets {
+            current,
+            days_1_30,
+            days_31_60,
+            days_61_90,
+            days_over_90,
+            totals,
+        }
+    }
+
+    pub fn total(&self, currency_id: &CurrencyId) -> Decimal {
+        amount(&self.totals, currency_id)
+    }
+}
+
+/// Outstanding invoices of a contact with their aging, invoices without a contact account are on
+/// an unassigned line with no contact
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct AgingLine {
+    pub contact_id: Option<ContactId>,
+    pub invoices: Vec<InvoiceBalance>,
+    pub buckets: AgingBuckets,
+}
+
+/// Receivables or payables aging by contact as of a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct AgingReport {
+    pub balance_type: BalanceType,
+    pub date_time: OffsetDateTime,
+    pub lines: Vec<AgingLine>,
+    pub totals: AgingBuckets,
+}
+
+impl AgingReport {
+    pub fn new(ledger: &Ledger, balance_type: BalanceType, date_time: OffsetDateTime) -> Self {
+        let invoices: Vec<InvoiceBalance> = InvoiceBalance::outstanding(ledger, date_time)
+            .into_iter()
+            .filter(|invoice| invoice.balance_type == balance_type)
+            .collect();
+        let mut contact_invoices: BTreeMap<Option<ContactId>, Vec<InvoiceBalance>> =
+            BTreeMap::new();
+        for invoice in &invoices {
+            contact_invoices
+                .entry(invoice.contact_id)
+                .or_default()
+                .push(invoice.clone());
+        }
+        let lines = contact_invoices
+            .into_iter()
+            .map(|(contact_id, invoices)| AgingLine {
+                contact_id,
+                buckets: AgingBuckets::new(&invoices, &date_time),
+                invoices,
+            })
+            .collect();
+        let totals = AgingBuckets::new(&invoices, &date_time);
+
+        AgingReport {
+            balance_type,
+            date_time,
+            lines,
+            totals,
+        }
+    }
+}
+
+fn currency_amounts(totals: &BTreeMap<CurrencyId, Decimal>) -> Vec<CurrencyAmount> {
+    totals
+        .iter()
+        .map(|(currency_id, amount)| CurrencyAmount::new(currency_id, *amount))
+        .collect()
+}
+
+// sum of additions minus sum of subtractions per currency
+fn net_amounts(
+    additions: Vec<&CurrencyAmount>,
+    subtractions: Vec<&CurrencyAmount>,
+) -> Vec<CurrencyAmount> {
+    let mut totals: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+    for addition in additions {
+        *totals.entry(addition.currency_id).or_default() += addition.amount;
+    }
+    for subtraction in subtractions {
+        *totals.entry(subtraction.currency_id).or_default() -= subtraction.amount;
+    }
+    currency_amounts(&totals)
+}
+
+fn amount(currency_amounts: &[CurrencyAmount], currency_id: &CurrencyId) -> Decimal {
+    currency_amounts
+        .iter()
+        .find(|currency_amount| currency_amount.currency_id.eq(currency_id))
+        .map(|currency_amount| currency_amount.amount)
+        .unwrap_or_default()
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
@@ -35,17 +566,55 @@ pub struct AccountTotals {
     pub account: Arc<Account>,
     pub debit_totals: Vec<CurrencyAmount>,
     pub credit_totals: Vec<CurrencyAmount>,
+    pub balances: Vec<CurrencyAmount>,
     pub child_account_totals: Vec<AccountTotals>,
 }
 
 impl AccountTotals {
-    pub fn new(ledger: &Ledger, account: Arc<Account>) -> Self {
+    pub fn new(ledger: &Ledger, account: Arc<Account>, date_time: OffsetDateTime) -> Self {
+        AccountTotals::new_period(ledger, account, None, date_time)
+    }
+
+    pub fn new_period(
+        ledger: &Ledger,
+        account: Arc<Account>,
+        start_date_time: Option<OffsetDateTime>,
+        end_date_time: OffsetDateTime,
+    ) -> Self {
+        AccountTotals::new_totals(ledger, account, start_date_time, end_date_time, true)
+    }
+
+    // totals without period close entries, ie. for the income statement of a closed period
+    pub fn new_period_before_close(
+        ledger: &Ledger,
+        account: Arc<Account>,
+        start_date_time: Option<OffsetDateTime>,
+        end_date_time: OffsetDateTime,
+    ) -> Self {
+        AccountTotals::new_totals(ledger, account, start_date_time, end_date_time, false)
+    }
+
+    fn new_totals(
+        ledger: &Ledger,
+        account: Arc<Account>,
+        start_date_time: Option<OffsetDateTime>,
+        end_date_time: OffsetDateTime,
+        include_period_close: bool,
+    ) -> Self {
         let child_ids = ledger.child_ids(&account);
         let child_account_totals: Vec<AccountTotals> = child_ids
             .iter()
             .map(|account_id| ledger.get_account(account_id))
             .flatten()
-            .map(|account| AccountTotals::new(&ledger, account))
+            .map(|account| {
+                AccountTotals::new_totals(
+                    ledger,
+                    account,
+                    start_date_time,
+                    end_date_time,
+                    include_period_close,
+                )
+            })
             .collect();
         let child_totals: [BTreeMap<CurrencyId, Decimal>; 2] = child_account_totals.iter().fold(
             [BTreeMap::new(), BTreeMap::new()],
@@ -83,49 +652,18 @@ impl AccountTotals {
             });
 
         let account_entries: Vec<Arc<LedgerEntry>> = ledger
-            .get_account_entries(&account.id)
-            .iter()
-            .flatten()
-            .cloned()
+            .get_account_entries_period(&account.id, start_date_time.as_ref(), &end_date_time)
+            .into_iter()
+            .filter(|entry| {
+                include_period_close
+                    || ledger
+                        .get_transaction(&entry.transaction_id)
+                        .map(|t| t.transaction_type != TransactionType::PeriodClose)
+                        .unwrap_or(true)
+            })
             .collect();
 
-        let account_totals: [BTreeMap<CurrencyId, Decimal>; 2] = account_entries
-            .iter()
-            .cloned()
-            .fold([BTreeMap::new(), BTreeMap::new()], |mut acc, entry| {
-                let currency_amount = entry.currency_amount.clone();
-                match entry.entry_type {
-                    EntryType::Debit => {
-                        let new_total = acc[0]
-                            .entry(currency_amount.currency_id)
-                            .or_default()
-                            .add(currency_amount.amount);
-                        acc[0].insert(currency_amount.currency_id, new_total);
-                    }
-                    EntryType::Credit => {
-                        let new_total = acc[1]
-                            .entry(currency_amount.currency_id)
-                            .or_default()
-                            .add(currency_amount.amount);
-                        acc[1].insert(currency_amount.currency_id, new_total);
-                    }
-                }
-                acc
-            });
-        let [debit_totals, credit_totals]: [Vec<CurrencyAmount>; 2] =
-            account_totals.map(|total_map| {
-                total_map
-                    .iter()
-                    .map(|(currency_id, amount)| {
-                        let currency_id = currency_id.clone();
-                        let amount = amount.clone();
-                        CurrencyAmount {
-                            currency_id,
-                            amount,
-                        }
-                    })
-                    .collect()
-            });
+        let [debit_totals, credit_totals] = AccountTotals::entry_totals(&account_entries);
 
         let mut debit_totals_appended: Vec<CurrencyAmount> = debit_totals.clone();
         debit_totals_appended.append(child_debit_totals.clone().as_mut());
@@ -159,25 +697,132 @@ impl AccountTotals {
                     .collect()
             });
 
+        let balances = AccountTotals::net_balances(
+            &account.account_category.normal_entry_type(),
+            &debit_totals,
+            &credit_totals,
+        );
+
         AccountTotals {
             account,
             debit_totals,
             credit_totals,
+            balances,
             child_account_totals,
         }
     }
+
+    // sum of balances converted to a currency at the exchange rates at date time
+    pub fn converted_balance(
+        &self,
+        ledger: &Ledger,
+        currency_id: &CurrencyId,
+        date_time: &OffsetDateTime,
+    ) -> Result<CurrencyAmount, Error> {
+        let mut amount = Decimal::ZERO;
+        for balance in &self.balances {
+            amount += ledger.convert(balance, currency_id, date_time)?.amount;
+        }
+        Ok(CurrencyAmount::new(currency_id, amount))
+    }
+
+    // debit and credit totals per currency of ledger entries
+    pub fn entry_totals(entries: &[Arc<LedgerEntry>]) -> [Vec<CurrencyAmount>; 2] {
+        let account_totals: [BTreeMap<CurrencyId, Decimal>; 2] =
+            entries
+                .iter()
+                .cloned()
+                .fold([BTreeMap::new(), BTreeMap::new()], |mut acc, entry| {
+                    let currency_amount = entry.currency_amount.clone();
+                    match entry.entry_type {
+                        EntryType::Debit => {
+                            let new_total = acc[0]
+                                .entry(currency_amount.currency_id)
+                                .or_default()
+                                .add(currency_amount.amount);
+                            acc[0].insert(currency_amount.currency_id, new_total);
+                        }
+                        EntryType::Credit => {
+                            let new_total = acc[1]
+                                .entry(currency_amount.currency_id)
+                                .or_default()
+                                .add(currency_amount.amount);
+                            acc[1].insert(currency_amount.currency_id, new_total);
+                        }
+                    }
+                    acc
+                });
+        account_totals.map(|total_map| {
+            total_map
+                .iter()
+                .map(|(currency_id, amount)| {
+                    let currency_id = *currency_id;
+                    let amount = *amount;
+                    CurrencyAmount {
+                        currency_id,
+                        amount,
+                    }
+                })
+                .collect()
+        })
+    }
+
+    // net balance per currency, positive when the account's normal entry type total is larger
+    fn net_balances(
+        normal_entry_type: &EntryType,
+        debit_totals: &Vec<CurrencyAmount>,
+        credit_totals: &Vec<CurrencyAmount>,
+    ) -> Vec<CurrencyAmount> {
+        let mut balances: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+        for debit_total in debit_totals {
+            *balances.entry(debit_total.currency_id).or_default() += debit_total.amount;
+        }
+        for credit_total in credit_totals {
+            *balances.entry(credit_total.currency_id).or_default() -= credit_total.amount;
+        }
+        balances
+            .into_iter()
+            .map(|(currency_id, balance)| {
+                let amount = match normal_entry_type {
+                    EntryType::Debit => balance,
+                    EntryType::Credit => -balance,
+                };
+                CurrencyAmount {
+                    currency_id,
+                    amount,
+                }
+            })
+            .collect()
+    }
+
+    pub fn balance(&self, currency_id: &CurrencyId) -> Decimal {
+        amount(&self.balances, currency_id)
+    }
+
+    // balance rounded to the currency's scale for display
+    pub fn balance_money(&self, currency: &Arc<Currency>) -> Money {
+        Money::rounded(currency, self.balance(&currency.id), RoundingMode::HalfEven)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::journal::test_entries;
     use crate::journal::AccountCategory::{BalanceSheet, IncomeStatement};
+    use crate::journal::Action::{AddAccount, AddExchangeRate, AddTransaction, ClosePeriod};
     use crate::journal::BalanceSheetCategory::{Asset, Equity, Liability};
     use crate::journal::IncomeStatementCategory::{OperatingExpense, OperatingRevenue};
+    use crate::journal::{
+        test_entries, Account, AccountType, CurrencyAmount, EntryType, ExchangeRate, JournalEntry,
+        LedgerEntry, PaymentMethod, PaymentTerms, PeriodClose, Transaction, TransactionType,
+    };
+    use crate::ledger::invoice::BalanceType;
+    use crate::ledger::report;
     use crate::ledger::report::Report;
     use crate::ledger::test::setup;
     use crate::ledger::OrganizationLedgers;
     use rust_decimal::Decimal;
+    use std::sync::Arc;
+    use time::macros::datetime;
     use time::OffsetDateTime;
 
     #[test]
@@ -277,4 +922,586 @@ mod test {
             .amount;
         assert_eq!(Decimal::new(8_000_00, 2), account0_credits0_amount);
     }
+
+    #[test]
+    fn test_converted_report() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        for (datetime, rate) in [
+            (datetime!(2022-01-01 00:00 UTC), Decimal::new(40_000, 0)),
+            (datetime!(2022-02-01 00:00 UTC), Decimal::new(50_000, 0)),
+        ] {
+            let exchange_rate =
+                ExchangeRate::new(&btc_id, &usd_id, rate, datetime, "test".to_string());
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddExchangeRate { exchange_rate },
+                ))
+                .expect("add exchange rate");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let organization = organization_ledgers
+            .get_organization(&organization_id)
+            .expect("organization");
+        assert_eq!(organization.functional_currency_id, usd_id);
+
+        let asset_account_id = ledger
+            .get_root_account(BalanceSheet(Asset))
+            .expect("Asset account");
+        let report = Report::new_converted(
+            &ledger,
+            organization,
+            None,
+            datetime!(2022-03-01 00:00 UTC),
+            vec![asset_account_id],
+            Some(&btc_id),
+        )
+        .expect("converted report");
+        assert_eq!(report.reporting_currency_id, Some(btc_id));
+        let converted_balance = report.converted_balances.get(0).expect("converted balance");
+        assert_eq!(converted_balance.amount, Decimal::new(36, 2));
+
+        let report = Report::new_converted(
+            &ledger,
+            organization,
+            None,
+            datetime!(2022-01-31 00:00 UTC),
+            vec![asset_account_id],
+            Some(&btc_id),
+        )
+        .expect("converted report");
+        let converted_balance = report.converted_balances.get(0).expect("converted balance");
+        assert_eq!(converted_balance.amount, Decimal::new(25, 2));
+
+        // defaults to the functional currency
+        let report = Report::new_converted(
+            &ledger,
+            organization,
+            None,
+            datetime!(2022-03-01 00:00 UTC),
+            vec![asset_account_id],
+            None,
+        )
+        .expect("converted report");
+        assert_eq!(report.reporting_currency_id, Some(usd_id));
+        let converted_balance = report.converted_balances.get(0).expect("converted balance");
+        assert_eq!(converted_balance.amount, Decimal::new(18_000_00, 2));
+    }
+
+    #[test]
+    fn test_report_date_time() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let asset_account_id = ledger
+            .get_root_account(BalanceSheet(Asset))
+            .expect("Asset account");
+        let revenue_account_id = ledger
+            .get_root_account(IncomeStatement(OperatingRevenue))
+            .expect("Revenue account");
+
+        let report = Report::new(
+            &ledger,
+            datetime!(2022-01-31 23:59 UTC),
+            vec![asset_account_id, revenue_account_id],
+        );
+        let asset_totals = report.account_totals.get(0).expect("asset totals");
+        assert_eq!(
+            Decimal::new(10_000_00, 2),
+            asset_totals.debit_totals.get(0).expect("debits 0").amount
+        );
+        let revenue_totals = report.account_totals.get(1).expect("revenue totals");
+        assert!(revenue_totals.credit_totals.is_empty());
+
+        let report = Report::new(
+            &ledger,
+            datetime!(2022-01-03 09:00 UTC),
+            vec![asset_account_id],
+        );
+        let asset_totals = report.account_totals.get(0).expect("asset totals");
+        assert_eq!(
+            Decimal::new(10_000_00, 2),
+            asset_totals.debit_totals.get(0).expect("debits 0").amount
+        );
+
+        let report = Report::new_period(
+            &ledger,
+            Some(datetime!(2022-02-01 00:00 UTC)),
+            datetime!(2022-02-28 23:59 UTC),
+            vec![asset_account_id, revenue_account_id],
+        );
+        let asset_totals = report.account_totals.get(0).expect("asset totals");
+        assert_eq!(
+            Decimal::new(8_000_00, 2),
+            asset_totals.debit_totals.get(0).expect("debits 0").amount
+        );
+        let revenue_totals = report.account_totals.get(1).expect("revenue totals");
+        assert_eq!(
+            Decimal::new(8_000_00, 2),
+            revenue_totals
+                .credit_totals
+                .get(0)
+                .expect("credits 0")
+                .amount
+        );
+    }
+
+    #[test]
+    fn test_account_balances() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let account_ids = [
+            BalanceSheet(Asset),
+            BalanceSheet(Equity),
+            IncomeStatement(OperatingRevenue),
+        ]
+        .map(|category| ledger.get_root_account(category).expect("root account"));
+        let report = Report::new(&ledger, OffsetDateTime::now_utc(), account_ids.to_vec());
+
+        let [asset_totals, equity_totals, revenue_totals] = [0, 1, 2].map(|index| {
+            report
+                .account_totals
+                .get(index)
+                .expect("account totals")
+                .clone()
+        });
+        assert_eq!(Decimal::new(18_000_00, 2), asset_totals.balance(&840));
+        assert_eq!(Decimal::new(10_000_00, 2), equity_totals.balance(&840));
+        assert_eq!(Decimal::new(8_000_00, 2), revenue_totals.balance(&840));
+        assert_eq!(Decimal::ZERO, revenue_totals.balance(&2009));
+        let usd = ledger.get_currency(&840).expect("usd");
+        assert_eq!(
+            "USD 18,000.00",
+            asset_totals.balance_money(&usd).to_string()
+        );
+
+        let owner_totals = equity_totals
+            .child_account_totals
+            .iter()
+            .find(|totals| totals.account.description.eq("Owner 1"))
+            .expect("owner totals");
+        assert_eq!(Decimal::new(10_000_00, 2), owner_totals.balance(&840));
+    }
+
+    #[test]
+    fn test_balance_sheet_statement() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let balance_sheet = report::BalanceSheet::new(&ledger, OffsetDateTime::now_utc());
+        assert_eq!(
+            Decimal::new(18_000_00, 2),
+            balance_sheet.assets.subtotal(&840)
+        );
+        assert_eq!(Decimal::ZERO, balance_sheet.liabilities.subtotal(&840));
+        assert_eq!(
+            Decimal::new(10_000_00, 2),
+            balance_sheet.equity.subtotal(&840)
+        );
+        assert_eq!(Decimal::new(18_000_00, 2), balance_sheet.total_equity(&840));
+        assert!(balance_sheet.is_balanced());
+    }
+
+    #[test]
+    fn test_income_statement_net_income() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let income_statement = report::IncomeStatement::new(
+            &ledger,
+            Some(datetime!(2022-01-01 00:00 UTC)),
+            datetime!(2022-12-31 23:59 UTC),
+        );
+        assert_eq!(
+            Decimal::new(8_000_00, 2),
+            income_statement.operating_revenue.subtotal(&840)
+        );
+        assert_eq!(
+            Decimal::ZERO,
+            income_statement.operating_expenses.subtotal(&840)
+        );
+        assert_eq!(Decimal::new(8_000_00, 2), income_statement.net_income(&840));
+
+        let income_statement = report::IncomeStatement::new(
+            &ledger,
+            Some(datetime!(2022-01-01 00:00 UTC)),
+            datetime!(2022-01-31 23:59 UTC),
+        );
+        assert_eq!(Decimal::ZERO, income_statement.net_income(&840));
+    }
+
+    #[test]
+    fn test_trial_balance() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let trial_balance = report::TrialBalance::new(&ledger, OffsetDateTime::now_utc());
+        assert_eq!(trial_balance.lines.len(), test_entries.accounts.len());
+        assert_eq!(
+            trial_balance.lines.get(0).expect("first line").full_number,
+            vec![100]
+        );
+        assert_eq!(
+            trial_balance.lines.get(1).expect("second line").full_number,
+            vec![100, 100]
+        );
+        let debit_total = trial_balance.debit_totals.get(0).expect("debit total");
+        assert_eq!(Decimal::new(18_000_00, 2), debit_total.amount);
+        let credit_total = trial_balance.credit_totals.get(0).expect("credit total");
+        assert_eq!(Decimal::new(18_000_00, 2), credit_total.amount);
+        assert!(trial_balance.is_balanced());
+
+        let trial_balance = report::TrialBalance::new(&ledger, datetime!(2022-01-31 23:59 UTC));
+        let debit_total = trial_balance.debit_totals.get(0).expect("debit total");
+        assert_eq!(Decimal::new(10_000_00, 2), debit_total.amount);
+        assert!(trial_balance.is_balanced());
+    }
+
+    #[test]
+    fn test_account_register() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let bank_account = Arc::new(test_entries.accounts.get(6).expect("bank").clone());
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let register = report::AccountRegister::new(
+            &ledger,
+            bank_account.clone(),
+            false,
+            None,
+            OffsetDateTime::now_utc(),
+        );
+        assert!(register.opening_balances.is_empty());
+        assert_eq!(register.lines.len(), 2);
+        let line = register.lines.get(0).expect("first line");
+        assert_eq!(line.counter_account_ids, vec![owner_account_id]);
+        assert_eq!(
+            Decimal::new(10_000_00, 2),
+            line.balances.get(0).expect("balance").amount
+        );
+        let closing_balance = register.closing_balances.get(0).expect("closing balance");
+        assert_eq!(Decimal::new(18_000_00, 2), closing_balance.amount);
+
+        let assets_account = ledger
+            .get_account(&bank_account.parent_id.expect("parent id"))
+            .expect("assets account");
+        let register = report::AccountRegister::new(
+            &ledger,
+            assets_account,
+            true,
+            Some(datetime!(2022-02-01 00:00 UTC)),
+            datetime!(2022-02-28 23:59 UTC),
+        );
+        let opening_balance = register.opening_balances.get(0).expect("opening balance");
+        assert_eq!(Decimal::new(10_000_00, 2), opening_balance.amount);
+        assert_eq!(register.lines.len(), 1);
+        let closing_balance = register.closing_balances.get(0).expect("closing balance");
+        assert_eq!(Decimal::new(18_000_00, 2), closing_balance.amount);
+    }
+
+    #[test]
+    fn test_statements_after_close_period() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let equity = test_entries.accounts.get(2).expect("equity");
+        let retained_earnings = Account::new(
+            Some(&equity.id),
+            200,
+            "Retained Earnings".to_string(),
+            AccountType::LedgerAccount,
+            BalanceSheet(Equity),
+        );
+        let retained_earnings_id = retained_earnings.id;
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: retained_earnings,
+                },
+            ))
+            .expect("add retained earnings account");
+        let period_close =
+            PeriodClose::new_fiscal_year(&test_entries.organization, 2022, &retained_earnings_id);
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                ClosePeriod { period_close },
+            ))
+            .expect("close period");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let income_statement = report::IncomeStatement::new(
+            &ledger,
+            Some(datetime!(2022-01-01 00:00 UTC)),
+            datetime!(2022-12-31 23:59 UTC),
+        );
+        assert_eq!(Decimal::new(8_000_00, 2), income_statement.net_income(&840));
+
+        let balance_sheet = report::BalanceSheet::new(&ledger, datetime!(2023-01-31 00:00 UTC));
+        assert_eq!(
+            Decimal::new(18_000_00, 2),
+            balance_sheet.equity.subtotal(&840)
+        );
+        assert!(balance_sheet.current_earnings.is_empty());
+        assert!(balance_sheet.is_balanced());
+
+        let balance_sheet = report::BalanceSheet::new(&ledger, datetime!(2022-06-30 00:00 UTC));
+        assert_eq!(
+            Decimal::new(10_000_00, 2),
+            balance_sheet.equity.subtotal(&840)
+        );
+        assert_eq!(Decimal::new(18_000_00, 2), balance_sheet.total_equity(&840));
+        assert!(balance_sheet.is_balanced());
+
+        let trial_balance = report::TrialBalance::new(&ledger, OffsetDateTime::now_utc());
+        assert!(trial_balance.is_balanced());
+    }
+
+    #[test]
+    fn test_aging_report() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let liabilities = test_entries.accounts.get(1).expect("liabilities");
+        let supplies_account_id = test_entries.accounts.get(7).expect("office supplies").id;
+        let income_account_id = test_entries.accounts.get(8).expect("consulting income").id;
+        let customer = test_entries.contacts.get(0).expect("owner");
+        let vendor = test_entries.contacts.get(1).expect("bank");
+        let receivable_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Owner Receivable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: customer.id,
+            },
+            BalanceSheet(Asset),
+        );
+        let payable_account = Account::new(
+            Some(&liabilities.id),
+            300,
+            "Bank Payable".to_string(),
+            AccountType::ContactAccount {
+                contact_id: vendor.id,
+            },
+            BalanceSheet(Liability),
+        );
+        for account in [receivable_account.clone(), payable_account.clone()] {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddAccount { account },
+                ))
+                .expect("add account");
+        }
+
+        let net_days = |days| PaymentTerms::NetDays {
+            days,
+            late_fee_interest: Decimal::ZERO,
+        };
+        let invoices = [
+            (
+                datetime!(2022-01-01 09:00 UTC),
+                net_days(30),
+                receivable_account.id,
+                income_account_id,
+                CurrencyAmount::new(&usd_id, Decimal::new(100_00, 2)),
+            ),
+            (
+                datetime!(2022-03-01 09:00 UTC),
+                net_days(30),
+                receivable_account.id,
+                income_account_id,
+                CurrencyAmount::new(&usd_id, Decimal::new(200_00, 2)),
+            ),
+            (
+                datetime!(2022-02-01 09:00 UTC),
+                PaymentTerms::ImmediatePayment,
+                receivable_account.id,
+                income_account_id,
+                CurrencyAmount::new(&btc_id, Decimal::new(100_000, 8)),
+            ),
+            (
+                datetime!(2022-03-15 09:00 UTC),
+                net_days(15),
+                supplies_account_id,
+                payable_account.id,
+                CurrencyAmount::new(&usd_id, Decimal::new(300_00, 2)),
+            ),
+        ];
+        for (datetime, payment_terms, debit_account_id, credit_account_id, amount) in invoices {
+            let transaction_type = if credit_account_id == payable_account.id {
+                TransactionType::Bill {
+                    payment_method: PaymentMethod::Cash,
+                    payment_terms,
+                    payments: vec![],
+                    line_items: vec![],
+                }
+            } else {
+                TransactionType::Invoice {
+                    payment_method: PaymentMethod::Cash,
+                    payment_terms,
+                    payments: vec![],
+                    line_items: vec![],
+                }
+            };
+            let transaction = Transaction::new(datetime, "Invoice".to_string(), transaction_type);
+            let ledger_entries = vec![
+                LedgerEntry::new(
+                    &transaction.id,
+                    EntryType::Debit,
+                    &debit_account_id,
+                    amount.clone(),
+                    None,
+                ),
+                LedgerEntry::new(
+                    &transaction.id,
+                    EntryType::Credit,
+                    &credit_account_id,
+                    amount,
+                    None,
+                ),
+            ];
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddTransaction {
+                        transaction,
+                        ledger_entries,
+                    },
+                ))
+                .expect("add invoice");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let receivables = report::AgingReport::new(
+            &ledger,
+            BalanceType::Receivable,
+            datetime!(2022-04-15 09:00 UTC),
+        );
+        assert_eq!(receivables.lines.len(), 1);
+        let line = receivables.lines.get(0).expect("customer line");
+        assert_eq!(line.contact_id, Some(customer.id));
+        assert_eq!(line.invoices.len(), 3);
+        assert!(line.buckets.current.is_empty());
+        assert_eq!(
+            line.buckets.days_1_30,
+            vec![CurrencyAmount::new(&usd_id, Decimal::new(200_00, 2))]
+        );
+        assert_eq!(
+            line.buckets.days_61_90,
+            vec![
+                CurrencyAmount::new(&usd_id, Decimal::new(100_00, 2)),
+                CurrencyAmount::new(&btc_id, Decimal::new(100_000, 8)),
+            ]
+        );
+        assert_eq!(receivables.totals.total(&usd_id), Decimal::new(300_00, 2));
+        assert_eq!(receivables.totals.total(&btc_id), Decimal::new(100_000, 8));
+
+        let receivables = report::AgingReport::new(
+            &ledger,
+            BalanceType::Receivable,
+            datetime!(2022-03-15 09:00 UTC),
+        );
+        assert_eq!(
+            receivables.totals.current,
+            vec![CurrencyAmount::new(&usd_id, Decimal::new(200_00, 2))]
+        );
+        assert_eq!(
+            receivables.totals.days_31_60,
+            vec![
+                CurrencyAmount::new(&usd_id, Decimal::new(100_00, 2)),
+                CurrencyAmount::new(&btc_id, Decimal::new(100_000, 8)),
+            ]
+        );
+
+        let payables = report::AgingReport::new(
+            &ledger,
+            BalanceType::Payable,
+            datetime!(2022-04-15 09:00 UTC),
+        );
+        assert_eq!(payables.lines.len(), 1);
+        assert_eq!(
+            payables.lines.get(0).expect("vendor").contact_id,
+            Some(vendor.id)
+        );
+        assert_eq!(
+            payables.totals.days_1_30,
+            vec![CurrencyAmount::new(&usd_id, Decimal::new(300_00, 2))]
+        );
+    }
 }
@@ -0,0 +1,500 @@
+use crate::journal::money::{Money, RoundingMode};
+use crate::journal::BalanceSheetCategory::{Asset, Liability};
+use crate::journal::{
+    AccountCategory, AccountId, Action, CurrencyId, EntryType, LedgerEntry, Transaction,
+    TransactionType,
+};
+use crate::ledger::cost_basis::PriceSource;
+use crate::ledger::{Error, Ledger};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+/// Functional currency values of a monetary account's foreign currency balance, amounts are
+/// positive for debit balances
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct RevaluationLine {
+    pub account_id: AccountId,
+    pub currency_id: CurrencyId,
+    pub balance: Decimal,
+    pub historical_value: Decimal,
+    pub revalued: Decimal,
+    pub current_value: Decimal,
+    pub adjustment: Decimal,
+}
+
+/// Unrealized exchange gains and losses of asset and liability accounts at a date time
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Revaluation {
+    pub date_time: OffsetDateTime,
+    pub functional_currency_id: CurrencyId,
+    pub lines: Vec<RevaluationLine>,
+}
+
+impl Revaluation {
+    // historical values use the rate at each transaction, current values the rate at date time
+    pub fn new<P: PriceSource>(
+        ledger: &Ledger,
+        functional_currency_id: &CurrencyId,
+        date_time: OffsetDateTime,
+        rates: &P,
+    ) -> Result<Self, Error> {
+        let scale = ledger
+            .get_currency(functional_currency_id)
+            .ok_or(Error::MissingCurrency(*functional_currency_id))?
+            .scale;
+        let rate = |currency_id: &CurrencyId, datetime: &OffsetDateTime| {
+            rates
+                .price(currency_id, functional_currency_id, datetime)
+                .ok_or(Error::MissingExchangeRate(
+                    *currency_id,
+                    *functional_currency_id,
+                ))
+        };
+
+        let mut lines = Vec::new();
+        let monetary_accounts = ledger.accounts().into_iter().filter(|account| {
+            matches!(
+                account.account_category,
+                AccountCategory::BalanceSheet(Asset) | AccountCategory::BalanceSheet(Liability)
+            )
+        });
+        for account in monetary_accounts {
+            let mut balances: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+            let mut historical_values: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+            let mut revalued: BTreeMap<CurrencyId, Decimal> = BTreeMap::new();
+            for entry in ledger.get_account_entries_period(&account.id, None, &date_time) {
+                let transaction = ledger
+                    .get_transaction(&entry.transaction_id)
+                    .expect("transaction");
+                let currency_id = entry.currency_amount.currency_id;
+                let amount = match entry.entry_type {
+                    EntryType::Debit => entry.currency_amount.amount,
+                    EntryType::Credit => -entry.currency_amount.amount,
+                };
+                if currency_id == *functional_currency_id {
+                    if let Some(revalued_currency_id) = revaluation_currency(ledger, &transaction) {
+                        *revalued.entry(revalued_currency_id).or_default() += amount;
+                    }
+                    continue;
+                }
+                *balances.entry(currency_id).or_default() += amount;
+                *historical_values.entry(currency_id).or_default() +=
+                    amount * rate(&currency_id, &transaction.datetime)?;
+            }
+            for (currency_id, balance) in balances {
+                let historical_value = historical_values[&currency_id].round_dp(scale);
+                let revalued = revalued.get(&currency_id).cloned().unwrap_or_default();
+                let current_value = (balance * rate(&currency_id, &date_time)?).round_dp(scale);
+                let adjustment = current_value - historical_value - revalued;
+                lines.push(RevaluationLine {
+                    account_id: account.id,
+                    currency_id,
+                    balance,
+                    historical_value,
+                    revalued,
+                    current_value,
+                    adjustment,
+                });
+            }
+        }
+
+        Ok(Revaluation {
+            date_time,
+            functional_currency_id: *functional_currency_id,
+            lines,
+        })
+    }
+
+    pub fn adjustment(&self) -> Decimal {
+        self.lines.iter().map(|line| line.adjustment).sum()
+    }
+
+    // revaluation transaction for each foreign currency offset in the gain or loss account, and
+    // its reversal at the start of the next period if a reversal date time is given
+    pub fn actions(
+        &self,
+        ledger: &Ledger,
+        gain_loss_account_id: &AccountId,
+        reversal_date_time: Option<OffsetDateTime>,
+    ) -> Result<Vec<Action>, Error> {
+        ledger.account_exists(gain_loss_account_id)?;
+        let functional_currency = ledger
+            .get_currency(&self.functional_currency_id)
+            .ok_or(Error::MissingCurrency(self.functional_currency_id))?;
+        let mut currency_lines: BTreeMap<CurrencyId, Vec<&RevaluationLine>> = BTreeMap::new();
+        for line in self.lines.iter().filter(|line| !line.adjustment.is_zero()) {
+            currency_lines
+                .entry(line.currency_id)
+                .or_default()
+                .push(line);
+        }
+
+        let mut actions = Vec::new();
+        for (currency_id, lines) in currency_lines {
+            let transaction = Transaction::new(
+                self.date_time,
+                format!("Revaluation at {}", self.date_time),
+                TransactionType::Revaluation { currency_id },
+            );
+            let entry = |account_id: &AccountId, amount: Decimal, description: &str| {
+                LedgerEntry::from_money(
+                    &transaction.id,
+                    account_id,
+                    &Money::rounded(&functional_currency, amount, RoundingMode::HalfEven),
+                    Some(description.to_string()),
+                )
+            };
+            let mut ledger_entries: Vec<LedgerEntry> = lines
+                .iter()
+                .map(|line| entry(&line.account_id, line.adjustment, "Revaluation"))
+                .collect();
+            let adjustment: Decimal = lines.iter().map(|line| line.adjustment).sum();
+            if !adjustment.is_zero() {
+                ledger_entries.push(entry(
+                    gain_loss_account_id,
+                    -adjustment,
+                    "Unrealized exchange gain or loss",
+                ));
+            }
+
+            let transaction_id = transaction.id;
+            actions.push(Action::AddTransaction {
+                transaction,
+                ledger_entries,
+            });
+            if let Some(reversal_date_time) = reversal_date_time {
+                let reversal = Transaction::new_reversal(
+                    reversal_date_time,
+                    format!("Reverse revaluation at {}", self.date_time),
+                    &transaction_id,
+                );
+                actions.push(Action::ReverseTransaction {
+                    transaction: reversal,
+                });
+            }
+        }
+        Ok(actions)
+    }
+}
+
+// foreign currency of revaluation transactions and their reversals
+fn revaluation_currency(ledger: &Ledger, transaction: &Transaction) -> Option<CurrencyId> {
+    match &transaction.transaction_type {
+        TransactionType::Revaluation { currency_id } => Some(*currency_id),
+        TransactionType::Reversal { transaction_id } => ledger
+            .get_transaction(transaction_id)
+            .and_then(|reversed| match reversed.transaction_type {
+                TransactionType::Revaluation { currency_id } => Some(currency_id),
+                _ => None,
+            }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::journal::Action::{AddAccount, AddCurrency, AddExchangeRate, AddTransaction};
+    use crate::journal::{
+        test_entries, Account, AccountCategory, AccountType, BalanceSheetCategory, Currency,
+        CurrencyAmount, CurrencyId, EntryType, ExchangeRate, IncomeStatementCategory, JournalEntry,
+        LedgerEntry, Transaction, TransactionType,
+    };
+    use crate::ledger::report::AccountTotals;
+    use crate::ledger::revaluation::Revaluation;
+    use crate::ledger::test::setup;
+    use crate::ledger::OrganizationLedgers;
+    use rust_decimal::Decimal;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_revaluation() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let btc_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Bitcoin Holdings".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        let gain_loss_account = Account::new(
+            None,
+            600,
+            "Exchange Gains and Losses".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::IncomeStatement(IncomeStatementCategory::NonOperatingRevenue),
+        );
+        let mut actions = vec![
+            AddAccount {
+                account: btc_account.clone(),
+            },
+            AddAccount {
+                account: gain_loss_account.clone(),
+            },
+        ];
+        for (datetime, rate) in [
+            (datetime!(2022-01-01 00:00 UTC), Decimal::new(40_000, 0)),
+            (datetime!(2022-03-31 00:00 UTC), Decimal::new(50_000, 0)),
+            (datetime!(2022-04-30 00:00 UTC), Decimal::new(45_000, 0)),
+        ] {
+            actions.push(AddExchangeRate {
+                exchange_rate: ExchangeRate::new(
+                    &btc_id,
+                    &usd_id,
+                    rate,
+                    datetime,
+                    "test".to_string(),
+                ),
+            });
+        }
+        let transaction = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Owner bitcoin contribution".to_string(),
+            TransactionType::LedgerAdjustment,
+        );
+        let amount = CurrencyAmount::new(&btc_id, Decimal::ONE);
+        let ledger_entries = vec![
+            LedgerEntry::new(
+                &transaction.id,
+                EntryType::Debit,
+                &btc_account.id,
+                amount.clone(),
+                None,
+            ),
+            LedgerEntry::new(
+                &transaction.id,
+                EntryType::Credit,
+                &owner_account_id,
+                amount,
+                None,
+            ),
+        ];
+        actions.push(AddTransaction {
+            transaction,
+            ledger_entries,
+        });
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add journal entry");
+        }
+
+        // revalue at period end and reverse at the next period start
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let revaluation =
+            Revaluation::new(&ledger, &usd_id, datetime!(2022-03-31 23:59 UTC), ledger)
+                .expect("revaluation");
+        assert_eq!(revaluation.lines.len(), 1);
+        assert_eq!(revaluation.adjustment(), Decimal::new(10_000, 0));
+        let actions = revaluation
+            .actions(
+                &ledger,
+                &gain_loss_account.id,
+                Some(datetime!(2022-04-01 00:00 UTC)),
+            )
+            .expect("actions");
+        assert_eq!(actions.len(), 2);
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add revaluation");
+        }
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let btc_account = ledger.get_account(&btc_account.id).expect("btc account");
+        let totals = AccountTotals::new(
+            &ledger,
+            btc_account.clone(),
+            datetime!(2022-03-31 23:59 UTC),
+        );
+        assert_eq!(totals.balance(&usd_id), Decimal::new(10_000, 0));
+        let totals = AccountTotals::new(
+            &ledger,
+            btc_account.clone(),
+            datetime!(2022-04-01 00:00 UTC),
+        );
+        assert_eq!(totals.balance(&usd_id), Decimal::ZERO);
+
+        // already revalued amounts are not revalued again
+        let revaluation =
+            Revaluation::new(&ledger, &usd_id, datetime!(2022-03-31 23:59 UTC), ledger)
+                .expect("revaluation");
+        assert_eq!(revaluation.adjustment(), Decimal::ZERO);
+        let actions = revaluation
+            .actions(&ledger, &gain_loss_account.id, None)
+            .expect("actions");
+        assert!(actions.is_empty());
+
+        let revaluation =
+            Revaluation::new(&ledger, &usd_id, datetime!(2022-04-30 23:59 UTC), ledger)
+                .expect("revaluation");
+        assert_eq!(revaluation.adjustment(), Decimal::new(5_000, 0));
+    }
+
+    #[test]
+    fn test_revaluation_currencies() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let usd_id = test_entries.currencies.get(0).expect("usd").id;
+        let btc_id = test_entries.currencies.get(1).expect("btc").id;
+        let eur = Currency {
+            id: 978,
+            code: "EUR".to_string(),
+            scale: 2,
+            name: "Euro".to_string(),
+            units: vec![],
+        };
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let foreign_account = Account::new(
+            Some(&assets.id),
+            300,
+            "Foreign Holdings".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        );
+        let gain_loss_account = Account::new(
+            None,
+            600,
+            "Exchange Gains and Losses".to_string(),
+            AccountType::LedgerAccount,
+            AccountCategory::IncomeStatement(IncomeStatementCategory::NonOperatingRevenue),
+        );
+        let mut actions = vec![
+            AddCurrency {
+                currency: eur.clone(),
+            },
+            AddAccount {
+                account: foreign_account.clone(),
+            },
+            AddAccount {
+                account: gain_loss_account.clone(),
+            },
+        ];
+        for (currency_id, datetime, rate) in [
+            (
+                btc_id,
+                datetime!(2022-01-01 00:00 UTC),
+                Decimal::new(40_000, 0),
+            ),
+            (
+                btc_id,
+                datetime!(2022-03-31 00:00 UTC),
+                Decimal::new(50_000, 0),
+            ),
+            (
+                btc_id,
+                datetime!(2022-04-30 00:00 UTC),
+                Decimal::new(45_000, 0),
+            ),
+            (eur.id, datetime!(2022-01-01 00:00 UTC), Decimal::ONE),
+            (eur.id, datetime!(2022-03-31 00:00 UTC), Decimal::new(11, 1)),
+            (eur.id, datetime!(2022-04-30 00:00 UTC), Decimal::new(12, 1)),
+        ] {
+            actions.push(AddExchangeRate {
+                exchange_rate: ExchangeRate::new(
+                    &currency_id,
+                    &usd_id,
+                    rate,
+                    datetime,
+                    "test".to_string(),
+                ),
+            });
+        }
+        for amount in [
+            CurrencyAmount::new(&btc_id, Decimal::ONE),
+            CurrencyAmount::new(&eur.id, Decimal::new(1_000, 0)),
+        ] {
+            let transaction = Transaction::new(
+                datetime!(2022-03-01 09:00 UTC),
+                "Owner contribution".to_string(),
+                TransactionType::LedgerAdjustment,
+            );
+            let ledger_entries = vec![
+                LedgerEntry::new(
+                    &transaction.id,
+                    EntryType::Debit,
+                    &foreign_account.id,
+                    amount.clone(),
+                    None,
+                ),
+                LedgerEntry::new(
+                    &transaction.id,
+                    EntryType::Credit,
+                    &owner_account_id,
+                    amount,
+                    None,
+                ),
+            ];
+            actions.push(AddTransaction {
+                transaction,
+                ledger_entries,
+            });
+        }
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add journal entry");
+        }
+
+        // each currency of an account is revalued separately
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let revaluation =
+            Revaluation::new(&ledger, &usd_id, datetime!(2022-03-31 23:59 UTC), ledger)
+                .expect("revaluation");
+        assert_eq!(revaluation.lines.len(), 2);
+        assert_eq!(revaluation.adjustment(), Decimal::new(10_100, 0));
+        let actions = revaluation
+            .actions(&ledger, &gain_loss_account.id, None)
+            .expect("actions");
+        assert_eq!(actions.len(), 2);
+        for action in actions {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(organization_id, action))
+                .expect("add revaluation");
+        }
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let revaluation =
+            Revaluation::new(&ledger, &usd_id, datetime!(2022-03-31 23:59 UTC), ledger)
+                .expect("revaluation");
+        assert!(revaluation
+            .lines
+            .iter()
+            .all(|line| line.adjustment.is_zero()));
+        let revaluation =
+            Revaluation::new(&ledger, &usd_id, datetime!(2022-04-30 23:59 UTC), ledger)
+                .expect("revaluation");
+        let adjustments: Vec<(CurrencyId, Decimal)> = revaluation
+            .lines
+            .iter()
+            .map(|line| (line.currency_id, line.adjustment))
+            .collect();
+        assert!(adjustments.contains(&(btc_id, Decimal::new(-5_000, 0))));
+        assert!(adjustments.contains(&(eur.id, Decimal::new(100, 0))));
+    }
+}
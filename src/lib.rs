@@ -6,3 +6,5 @@ pub use time::macros;
 
 pub mod journal;
 pub mod ledger;
+pub mod lightning;
+pub mod wallet;
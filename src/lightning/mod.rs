@@ -0,0 +1,378 @@
+use crate::journal::{btc_currency_id, CurrencyAmount, Payment, BTC_SCALE};
+use bech32::{u5, FromBase32};
+use bitcoin_hashes::hex::{FromHex, ToHex};
+use bitcoin_hashes::{sha256, Hash};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use time::{Duration, OffsetDateTime};
+
+/// Millisatoshi BTC scale, lightning amounts are in millisatoshis
+pub const MSAT_SCALE: u32 = 11;
+
+/// Default invoice expiry in seconds
+pub const DEFAULT_EXPIRY: u64 = 3600;
+
+// bech32 words in a recoverable signature
+const SIGNATURE_WORDS: usize = 104;
+// bech32 words in the invoice timestamp
+const TIMESTAMP_WORDS: usize = 7;
+// bech32 words in a 256 bit hash
+const HASH_WORDS: usize = 52;
+// bech32 words in a 33 byte public key
+const PUBKEY_WORDS: usize = 53;
+
+// tagged field types, the bech32 value of the field character
+const PAYMENT_HASH: u8 = 1; // p
+const DESCRIPTION: u8 = 13; // d
+const DESCRIPTION_HASH: u8 = 23; // h
+const EXPIRY: u8 = 6; // x
+const PAYEE: u8 = 19; // n
+const PAYMENT_SECRET: u8 = 16; // s
+
+// currency prefixes, longest prefix first
+const CURRENCY_PREFIXES: [&str; 4] = ["bcrt", "bc", "tbs", "tb"];
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    Bech32(String),
+    InvalidPrefix(String),
+    InvalidAmount(String),
+    InvalidLength,
+    InvalidField(u8),
+    MissingPaymentHash,
+    MissingDescription,
+    MissingAmount,
+    AmountMismatch(u64),
+    PaymentHashMismatch(String),
+    InvalidPreimage(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bech32(e) => write!(f, "bech32: {}", e),
+            Self::InvalidPrefix(p) => write!(f, "invalid prefix: {}", p),
+            Self::InvalidAmount(a) => write!(f, "invalid amount: {}", a),
+            Self::InvalidLength => write!(f, "invalid length"),
+            Self::InvalidField(t) => write!(f, "invalid field: {}", t),
+            Self::MissingPaymentHash => write!(f, "missing payment hash"),
+            Self::MissingDescription => write!(f, "missing description"),
+            Self::MissingAmount => write!(f, "missing amount"),
+            Self::AmountMismatch(a) => write!(f, "amount mismatch: {}", a),
+            Self::PaymentHashMismatch(h) => write!(f, "payment hash mismatch: {}", h),
+            Self::InvalidPreimage(p) => write!(f, "invalid preimage: {}", p),
+        }
+    }
+}
+
+impl std::convert::From<bech32::Error> for Error {
+    fn from(err: bech32::Error) -> Self {
+        Error::Bech32(err.to_string())
+    }
+}
+
+/// Parsed BOLT11 lightning invoice, the signature is not verified
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Bolt11Invoice {
+    pub invoice: String,
+    pub currency_prefix: String,
+    pub amount_msat: Option<u64>,
+    pub timestamp: OffsetDateTime,
+    pub payment_hash: String,
+    pub payment_secret: Option<String>,
+    pub description: Option<String>,
+    pub description_hash: Option<String>,
+    pub expiry: u64,
+    pub payee: Option<String>,
+}
+
+impl Bolt11Invoice {
+    pub fn parse(invoice: &str) -> Result<Self, Error> {
+        let invoice = invoice.trim();
+        let invoice = invoice
+            .strip_prefix("lightning:")
+            .or_else(|| invoice.strip_prefix("LIGHTNING:"))
+            .unwrap_or(invoice);
+        let (hrp, data, _variant) = bech32::decode(invoice)?;
+        let (currency_prefix, amount_msat) = parse_hrp(&hrp)?;
+        if data.len() < TIMESTAMP_WORDS + SIGNATURE_WORDS {
+            return Err(Error::InvalidLength);
+        }
+        let data = &data[..data.len() - SIGNATURE_WORDS];
+        let timestamp =
+            OffsetDateTime::from_unix_timestamp(words_to_u64(&data[..TIMESTAMP_WORDS]) as i64)
+                .map_err(|_| Error::InvalidLength)?;
+
+        let mut payment_hash = None;
+        let mut payment_secret = None;
+        let mut description = None;
+        let mut description_hash = None;
+        let mut expiry = DEFAULT_EXPIRY;
+        let mut payee = None;
+        let mut fields = &data[TIMESTAMP_WORDS..];
+        while !fields.is_empty() {
+            if fields.len() < 3 {
+                return Err(Error::InvalidLength);
+            }
+            let field_type = fields[0].to_u8();
+            let length = words_to_u64(&fields[1..3]) as usize;
+            if fields.len() < 3 + length {
+                return Err(Error::InvalidLength);
+            }
+            let field = &fields[3..3 + length];
+            fields = &fields[3 + length..];
+
+            // fields with unexpected lengths are skipped, the first valid field is used
+            match (field_type, length) {
+                (PAYMENT_HASH, HASH_WORDS) if payment_hash.is_none() => {
+                    payment_hash = Some(words_to_hex(field_type, field)?);
+                }
+                (PAYMENT_SECRET, HASH_WORDS) if payment_secret.is_none() => {
+                    payment_secret = Some(words_to_hex(field_type, field)?);
+                }
+                (DESCRIPTION_HASH, HASH_WORDS) if description_hash.is_none() => {
+                    description_hash = Some(words_to_hex(field_type, field)?);
+                }
+                (PAYEE, PUBKEY_WORDS) if payee.is_none() => {
+                    payee = Some(words_to_hex(field_type, field)?);
+                }
+                (DESCRIPTION, _) if description.is_none() => {
+                    let bytes = Vec::<u8>::from_base32(field)
+                        .map_err(|_| Error::InvalidField(field_type))?;
+                    let text =
+                        String::from_utf8(bytes).map_err(|_| Error::InvalidField(field_type))?;
+                    description = Some(text);
+                }
+                (EXPIRY, 1..=12) => {
+                    expiry = words_to_u64(field);
+                }
+                _ => {}
+            }
+        }
+        let payment_hash = payment_hash.ok_or(Error::MissingPaymentHash)?;
+        if description.is_none() && description_hash.is_none() {
+            return Err(Error::MissingDescription);
+        }
+
+        Ok(Bolt11Invoice {
+            invoice: invoice.to_lowercase(),
+            currency_prefix,
+            amount_msat,
+            timestamp,
+            payment_hash,
+            payment_secret,
+            description,
+            description_hash,
+            expiry,
+            payee,
+        })
+    }
+
+    pub fn expires_at(&self) -> OffsetDateTime {
+        self.timestamp + Duration::seconds(self.expiry as i64)
+    }
+
+    pub fn is_expired(&self, datetime: &OffsetDateTime) -> bool {
+        &self.expires_at() <= datetime
+    }
+
+    // payment of this invoice, amount required if the invoice has none
+    pub fn payment(
+        &self,
+        amount_msat: Option<u64>,
+        preimage: Option<String>,
+        settled: Option<OffsetDateTime>,
+    ) -> Result<Payment, Error> {
+        let amount_msat = match (self.amount_msat, amount_msat) {
+            (Some(invoice_amount), Some(amount)) if invoice_amount != amount => {
+                return Err(Error::AmountMismatch(amount))
+            }
+            (Some(amount), _) | (None, Some(amount)) => amount,
+            (None, None) => return Err(Error::MissingAmount),
+        };
+        if let Some(preimage) = &preimage {
+            preimage_valid(&self.payment_hash, preimage)?;
+        }
+        Ok(Payment::Lightning {
+            invoice: self.invoice.clone(),
+            payment_hash: self.payment_hash.clone(),
+            preimage,
+            amount_msat,
+            settled,
+        })
+    }
+}
+
+impl FromStr for Bolt11Invoice {
+    type Err = Error;
+
+    fn from_str(invoice: &str) -> Result<Self, Self::Err> {
+        Bolt11Invoice::parse(invoice)
+    }
+}
+
+// check a lightning payment's invoice, payment hash, amount and preimage agree
+pub fn payment_valid(payment: &Payment) -> Result<(), Error> {
+    if let Payment::Lightning {
+        invoice,
+        payment_hash,
+        preimage,
+        amount_msat,
+        ..
+    } = payment
+    {
+        let invoice = Bolt11Invoice::parse(invoice)?;
+        if &invoice.payment_hash != payment_hash {
+            return Err(Error::PaymentHashMismatch(payment_hash.clone()));
+        }
+        if invoice
+            .amount_msat
+            .is_some_and(|invoice_amount| &invoice_amount != amount_msat)
+        {
+            return Err(Error::AmountMismatch(*amount_msat));
+        }
+        if let Some(preimage) = preimage {
+            preimage_valid(payment_hash, preimage)?;
+        }
+    }
+    Ok(())
+}
+
+// payment hash must be the sha256 hash of the hex encoded preimage
+pub fn preimage_valid(payment_hash: &str, preimage: &str) -> Result<(), Error> {
+    let bytes =
+        Vec::<u8>::from_hex(preimage).map_err(|_| Error::InvalidPreimage(preimage.to_string()))?;
+    if sha256::Hash::hash(&bytes).to_hex() != payment_hash.to_lowercase() {
+        return Err(Error::InvalidPreimage(preimage.to_string()));
+    }
+    Ok(())
+}
+
+pub fn msats_to_btc(msats: u64) -> Decimal {
+    Decimal::new(msats as i64, MSAT_SCALE)
+}
+
+// BTC amount rounded to the BTC currency scale, midpoints rounded to the nearest even satoshi
+pub fn msats_to_btc_amount(msats: u64) -> CurrencyAmount {
+    let amount = msats_to_btc(msats)
+        .round_dp_with_strategy(BTC_SCALE, RoundingStrategy::MidpointNearestEven);
+    CurrencyAmount::new(&btc_currency_id(), amount)
+}
+
+// human readable part is "ln", the currency prefix and an optional amount with multiplier
+fn parse_hrp(hrp: &str) -> Result<(String, Option<u64>), Error> {
+    let rest = hrp
+        .strip_prefix("ln")
+        .ok_or(Error::InvalidPrefix(hrp.to_string()))?;
+    let currency_prefix = CURRENCY_PREFIXES
+        .iter()
+        .find(|prefix| rest.starts_with(*prefix))
+        .ok_or(Error::InvalidPrefix(hrp.to_string()))?;
+    let amount = &rest[currency_prefix.len()..];
+    if amount.is_empty() {
+        return Ok((currency_prefix.to_string(), None));
+    }
+
+    let invalid_amount = || Error::InvalidAmount(amount.to_string());
+    let (digits, multiplier) = match amount.chars().last() {
+        Some(c) if c.is_ascii_digit() => (amount, None),
+        Some(c) => (&amount[..amount.len() - 1], Some(c)),
+        None => return Err(invalid_amount()),
+    };
+    if digits.is_empty() || digits.starts_with('0') {
+        return Err(invalid_amount());
+    }
+    let value: u64 = digits.parse().map_err(|_| invalid_amount())?;
+    let amount_msat = match multiplier {
+        None => value.checked_mul(100_000_000_000),
+        Some('m') => value.checked_mul(100_000_000),
+        Some('u') => value.checked_mul(100_000),
+        Some('n') => value.checked_mul(100),
+        // pico-bitcoin amounts must be whole millisatoshis
+        Some('p') if value.is_multiple_of(10) => Some(value / 10),
+        _ => None,
+    }
+    .ok_or_else(invalid_amount)?;
+    Ok((currency_prefix.to_string(), Some(amount_msat)))
+}
+
+fn words_to_u64(words: &[u5]) -> u64 {
+    words
+        .iter()
+        .fold(0, |value, word| (value << 5) | word.to_u8() as u64)
+}
+
+fn words_to_hex(field_type: u8, words: &[u5]) -> Result<String, Error> {
+    let bytes = Vec::<u8>::from_base32(words).map_err(|_| Error::InvalidField(field_type))?;
+    Ok(bytes.to_hex())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::journal::Payment;
+    use crate::lightning::{
+        msats_to_btc_amount, payment_valid, preimage_valid, Bolt11Invoice, Error,
+    };
+    use rust_decimal::Decimal;
+    use time::macros::datetime;
+
+    pub(crate) const TEST_INVOICE: &str = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+
+    #[test]
+    fn test_parse_invoice() {
+        let invoice = Bolt11Invoice::parse(TEST_INVOICE).expect("invoice");
+        assert_eq!(invoice.currency_prefix, "bc");
+        assert_eq!(invoice.amount_msat, Some(250_000_000));
+        assert_eq!(invoice.timestamp, datetime!(2017-06-01 10:57:38 UTC));
+        assert_eq!(
+            invoice.payment_hash,
+            "0001020304050607080900010203040506070809000102030405060708090102"
+        );
+        assert_eq!(invoice.description, Some("1 cup coffee".to_string()));
+        assert_eq!(invoice.expiry, 60);
+        assert_eq!(invoice.expires_at(), datetime!(2017-06-01 10:58:38 UTC));
+
+        let invalid = TEST_INVOICE.replace("lnbc2500u", "lnbc2501u");
+        assert!(matches!(
+            Bolt11Invoice::parse(&invalid),
+            Err(Error::Bech32(_))
+        ));
+
+        let payment = invoice.payment(None, None, None).expect("payment");
+        assert!(payment_valid(&payment).is_ok());
+        let result = invoice.payment(Some(1_000), None, None);
+        assert!(matches!(result, Err(Error::AmountMismatch(1_000))));
+        let result = invoice.payment(None, Some("00".repeat(32)), None);
+        assert!(matches!(result, Err(Error::InvalidPreimage(_))));
+
+        let payment = Payment::Lightning {
+            invoice: invoice.invoice.clone(),
+            payment_hash: "00".repeat(32),
+            preimage: None,
+            amount_msat: 250_000_000,
+            settled: None,
+        };
+        assert!(matches!(
+            payment_valid(&payment),
+            Err(Error::PaymentHashMismatch(_))
+        ));
+
+        let payment_hash = "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925";
+        assert!(preimage_valid(payment_hash, &"00".repeat(32)).is_ok());
+        assert!(preimage_valid(payment_hash, &"01".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_msats_to_btc_amount() {
+        assert_eq!(
+            msats_to_btc_amount(250_000_000).amount,
+            Decimal::new(250_000, 8)
+        );
+        assert_eq!(msats_to_btc_amount(1_499).amount, Decimal::new(1, 8));
+        assert_eq!(msats_to_btc_amount(1_500).amount, Decimal::new(2, 8));
+        assert_eq!(msats_to_btc_amount(2_500).amount, Decimal::new(2, 8));
+        assert_eq!(msats_to_btc_amount(2_501).amount, Decimal::new(3, 8));
+    }
+}
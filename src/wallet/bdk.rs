@@ -0,0 +1,125 @@
+use crate::wallet::{ChainBackend, Error, WalletSnapshot, WalletTransaction, WalletUtxo};
+use bdk::bitcoin::Network;
+#[cfg(feature = "electrum")]
+use bdk::blockchain::ElectrumBlockchain;
+#[cfg(feature = "esplora")]
+use bdk::blockchain::EsploraBlockchain;
+use bdk::blockchain::{GetHeight, WalletSync};
+use bdk::database::MemoryDatabase;
+#[cfg(feature = "electrum")]
+use bdk::electrum_client::Client;
+use bdk::wallet::AddressIndex;
+use bdk::{KeychainKind, SyncOptions, Wallet};
+use time::OffsetDateTime;
+
+/// Esplora and electrum address stop gap
+#[cfg(feature = "esplora")]
+const STOP_GAP: usize = 20;
+
+/// Syncs a new in-memory bdk wallet for each descriptor from a bdk blockchain
+pub struct BdkBackend<B>
+where
+    B: WalletSync + GetHeight,
+{
+    blockchain: B,
+    network: Network,
+}
+
+impl<B> BdkBackend<B>
+where
+    B: WalletSync + GetHeight,
+{
+    pub fn new(blockchain: B, network: Network) -> Self {
+        BdkBackend {
+            blockchain,
+            network,
+        }
+    }
+}
+
+#[cfg(feature = "electrum")]
+impl BdkBackend<ElectrumBlockchain> {
+    pub fn electrum(url: &str, network: Network) -> Result<Self, Error> {
+        let client = Client::new(url)?;
+        Ok(BdkBackend::new(ElectrumBlockchain::from(client), network))
+    }
+}
+
+#[cfg(feature = "esplora")]
+impl BdkBackend<EsploraBlockchain> {
+    pub fn esplora(base_url: &str, network: Network) -> Self {
+        BdkBackend::new(EsploraBlockchain::new(base_url, STOP_GAP), network)
+    }
+}
+
+impl<B> ChainBackend for BdkBackend<B>
+where
+    B: WalletSync + GetHeight,
+{
+    fn sync(
+        &self,
+        descriptor: &str,
+        change_descriptor: Option<&str>,
+    ) -> Result<WalletSnapshot, Error> {
+        let wallet = Wallet::new(
+            descriptor,
+            change_descriptor,
+            self.network,
+            MemoryDatabase::new(),
+        )?;
+        wallet.sync(&self.blockchain, SyncOptions::default())?;
+
+        let balance = wallet.get_balance()?;
+        let utxos = wallet
+            .list_unspent()?
+            .iter()
+            .map(|utxo| WalletUtxo {
+                txid: utxo.outpoint.txid.to_string(),
+                vout: utxo.outpoint.vout,
+                value: utxo.txout.value,
+                change: utxo.keychain == KeychainKind::Internal,
+            })
+            .collect();
+        let transactions = wallet
+            .list_transactions(false)?
+            .iter()
+            .map(|details| WalletTransaction {
+                txid: details.txid.to_string(),
+                received: details.received,
+                sent: details.sent,
+                fee: details.fee,
+                confirmation_height: details.confirmation_time.as_ref().map(|c| c.height),
+                confirmation_time: details
+                    .confirmation_time
+                    .as_ref()
+                    .and_then(|c| OffsetDateTime::from_unix_timestamp(c.timestamp as i64).ok()),
+            })
+            .collect();
+
+        Ok(WalletSnapshot {
+            synced: OffsetDateTime::now_utc(),
+            balance,
+            utxos,
+            transactions,
+        })
+    }
+
+    fn address(&self, descriptor: &str, index: u32) -> Result<String, Error> {
+        let wallet = Wallet::new(descriptor, None, self.network, MemoryDatabase::new())?;
+        let address_info = wallet.get_address(AddressIndex::Peek(index))?;
+        Ok(address_info.address.to_string())
+    }
+}
+
+impl std::convert::From<bdk::Error> for Error {
+    fn from(err: bdk::Error) -> Self {
+        Error::Backend(err.to_string())
+    }
+}
+
+#[cfg(feature = "electrum")]
+impl std::convert::From<bdk::electrum_client::Error> for Error {
+    fn from(err: bdk::electrum_client::Error) -> Self {
+        Error::Backend(err.to_string())
+    }
+}
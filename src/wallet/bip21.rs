@@ -0,0 +1,134 @@
+use crate::journal::{PaymentMethod, Transaction, TransactionType};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// BIP21 bitcoin payment URI, amount in BTC
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct PaymentUri {
+    pub address: String,
+    pub amount: Option<Decimal>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+impl PaymentUri {
+    pub fn new(
+        address: String,
+        amount: Option<Decimal>,
+        label: Option<String>,
+        message: Option<String>,
+    ) -> Self {
+        PaymentUri {
+            address,
+            amount,
+            label,
+            message,
+        }
+    }
+
+    // payment uri for an invoice paid to a bitcoin address, with the description as message
+    pub fn for_invoice(
+        transaction: &Transaction,
+        amount: Option<Decimal>,
+        label: Option<String>,
+    ) -> Option<Self> {
+        match &transaction.transaction_type {
+            TransactionType::Invoice {
+                payment_method: PaymentMethod::Bitcoin { address, .. },
+                ..
+            } => Some(PaymentUri::new(
+                address.clone(),
+                amount,
+                label,
+                Some(transaction.description.clone()),
+            )),
+            _ => None,
+        }
+    }
+
+    // uppercase scheme and bech32 address so QR codes can use the smaller alphanumeric mode
+    pub fn qr_payload(&self) -> String {
+        let uri = self.to_string();
+        let lowercase_address = self.address.to_lowercase();
+        let bech32 = ["bc1", "tb1", "bcrt1"]
+            .iter()
+            .any(|hrp| lowercase_address.starts_with(hrp));
+        match uri.split_once('?') {
+            Some((address, params)) if bech32 => {
+                format!("{}?{}", address.to_uppercase(), params)
+            }
+            None if bech32 => uri.to_uppercase(),
+            _ => uri,
+        }
+    }
+}
+
+impl Display for PaymentUri {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bitcoin:{}", self.address)?;
+        let params: Vec<String> = [
+            (
+                "amount",
+                self.amount.map(|amount| amount.normalize().to_string()),
+            ),
+            ("label", self.label.as_deref().map(percent_encode)),
+            ("message", self.message.as_deref().map(percent_encode)),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|value| format!("{}={}", key, value)))
+        .collect();
+        if !params.is_empty() {
+            write!(f, "?{}", params.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+// percent encode all but unreserved characters
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::wallet::bip21::PaymentUri;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_payment_uri() {
+        let uri = PaymentUri::new(
+            "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            Some(Decimal::new(50_000, 8)),
+            Some("Test Org".to_string()),
+            Some("Invoice #1 & services".to_string()),
+        );
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.0005&label=Test%20Org&message=Invoice%20%231%20%26%20services"
+        );
+        assert_eq!(
+            uri.qr_payload(),
+            "BITCOIN:BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ?amount=0.0005&label=Test%20Org&message=Invoice%20%231%20%26%20services"
+        );
+
+        let uri = PaymentUri::new(
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_string(),
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            uri.qr_payload(),
+            "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"
+        );
+    }
+}
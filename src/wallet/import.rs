@@ -0,0 +1,537 @@
+use crate::journal::{
+    btc_currency_id, AccountId, Action, CurrencyAmount, EntryType, LedgerEntry, Transaction,
+    TransactionType,
+};
+use crate::ledger::Ledger;
+use crate::wallet::{sats_to_btc, Error, WalletSnapshot, WalletTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum TransferDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// Counter account for wallet transactions matching all of the rule's set conditions,
+/// amounts in satoshis
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ImportRule {
+    pub txid: Option<String>,
+    pub direction: Option<TransferDirection>,
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+    pub counter_account_id: AccountId,
+    pub description: Option<String>,
+}
+
+impl ImportRule {
+    pub fn new(counter_account_id: &AccountId) -> Self {
+        let counter_account_id = *counter_account_id;
+        ImportRule {
+            txid: None,
+            direction: None,
+            min_amount: None,
+            max_amount: None,
+            counter_account_id,
+            description: None,
+        }
+    }
+
+    pub fn matches(&self, direction: &TransferDirection, wallet_tx: &WalletTransaction) -> bool {
+        let amount = wallet_tx.net().unsigned_abs();
+        self.txid
+            .as_ref()
+            .is_none_or(|txid| txid == &wallet_tx.txid)
+            && self.direction.as_ref().is_none_or(|d| d == direction)
+            && self.min_amount.is_none_or(|min| amount >= min)
+            && self.max_amount.is_none_or(|max| amount <= max)
+    }
+}
+
+/// Counter accounts for imported wallet transactions, the first matching rule is used before
+/// the default incoming or outgoing account
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ImportConfig {
+    pub rules: Vec<ImportRule>,
+    pub incoming_account_id: AccountId,
+    pub outgoing_account_id: AccountId,
+    pub fee_account_id: AccountId,
+}
+
+impl ImportConfig {
+    pub fn new(
+        incoming_account_id: &AccountId,
+        outgoing_account_id: &AccountId,
+        fee_account_id: &AccountId,
+    ) -> Self {
+        ImportConfig {
+            rules: Vec::new(),
+            incoming_account_id: *incoming_account_id,
+            outgoing_account_id: *outgoing_account_id,
+            fee_account_id: *fee_account_id,
+        }
+    }
+}
+
+/// Transaction and ledger entries proposed for a wallet transaction
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ProposedTransaction {
+    pub transaction: Transaction,
+    pub ledger_entries: Vec<LedgerEntry>,
+}
+
+impl ProposedTransaction {
+    pub fn into_action(self) -> Action {
+        Action::AddTransaction {
+            transaction: self.transaction,
+            ledger_entries: self.ledger_entries,
+        }
+    }
+}
+
+/// Propose transactions for an account's confirmed wallet transactions whose txid is not yet in
+/// the ledger for the account, transfers to or from the organization's other synced bitcoin
+/// accounts are proposed once with entries in both accounts
+pub fn propose_transactions(
+    ledger: &Ledger,
+    account_id: &AccountId,
+    snapshots: &BTreeMap<AccountId, WalletSnapshot>,
+    config: &ImportConfig,
+) -> Result<Vec<ProposedTransaction>, Error> {
+    let counter_account_ids = config
+        .rules
+        .iter()
+        .map(|rule| &rule.counter_account_id)
+        .chain([
+            &config.incoming_account_id,
+            &config.outgoing_account_id,
+            &config.fee_account_id,
+        ]);
+    for counter_account_id in counter_account_ids {
+        if ledger.get_account(counter_account_id).is_none() {
+            return Err(Error::MissingAccount(*counter_account_id));
+        }
+        if counter_account_id == account_id {
+            return Err(Error::InvalidCounterAccount(*counter_account_id));
+        }
+    }
+    let bitcoin_account_ids = crate::wallet::bitcoin_account_ids(ledger);
+    if bitcoin_account_ids.iter().all(|id| id != account_id) {
+        return Err(Error::NotBitcoinAccount(*account_id));
+    }
+    let snapshot = snapshots
+        .get(account_id)
+        .ok_or(Error::MissingWallet(*account_id))?;
+
+    let proposed = snapshot
+        .transactions
+        .iter()
+        .filter(|wallet_tx| {
+            ledger
+                .get_txid_transaction(account_id, &wallet_tx.txid)
+                .is_none()
+        })
+        .filter_map(|wallet_tx| {
+            // the same txid with the opposite direction in another of the organization's wallets
+            let transfer = bitcoin_account_ids
+                .iter()
+                .filter(|id| *id != account_id)
+                .filter(|id| ledger.get_txid_transaction(id, &wallet_tx.txid).is_none())
+                .find_map(|id| {
+                    snapshots.get(id).and_then(|other_snapshot| {
+                        other_snapshot
+                            .transactions
+                            .iter()
+                            .find(|other_tx| {
+                                other_tx.txid == wallet_tx.txid
+                                    && other_tx.net().signum() == -wallet_tx.net().signum()
+                            })
+                            .map(|other_tx| (*id, other_tx))
+                    })
+                });
+            match transfer {
+                Some((other_account_id, other_tx)) => propose_transfer(
+                    (account_id, wallet_tx),
+                    (&other_account_id, other_tx),
+                    config,
+                ),
+                None => propose_transaction(account_id, wallet_tx, config),
+            }
+        })
+        .collect();
+    Ok(proposed)
+}
+
+// transfer from the sending wallet to the receiving wallet, with the sender's fee and any amount
+// sent to others
+fn propose_transfer(
+    wallet: (&AccountId, &WalletTransaction),
+    other_wallet: (&AccountId, &WalletTransaction),
+    config: &ImportConfig,
+) -> Option<ProposedTransaction> {
+    let ((sender_id, sent_tx), (receiver_id, received_tx)) = if wallet.1.net() < 0 {
+        (wallet, other_wallet)
+    } else {
+        (other_wallet, wallet)
+    };
+    let datetime = sent_tx
+        .confirmation_time
+        .or(received_tx.confirmation_time)?;
+    let transaction = Transaction::new(
+        datetime,
+        format!("Bitcoin transfer {}", sent_tx.txid),
+        TransactionType::OnChain {
+            txid: sent_tx.txid.clone(),
+        },
+    );
+
+    let btc_id = btc_currency_id();
+    let entry = |entry_type: EntryType, account_id: &AccountId, sats: u64, description: &str| {
+        LedgerEntry::new(
+            &transaction.id,
+            entry_type,
+            account_id,
+            CurrencyAmount::new(&btc_id, sats_to_btc(sats as i64)),
+            Some(description.to_string()),
+        )
+    };
+    let amount = sent_tx.net().unsigned_abs();
+    let received = received_tx.net().unsigned_abs().min(amount);
+    let fee = sent_tx.fee.unwrap_or_default().min(amount - received);
+    let mut ledger_entries = vec![
+        entry(EntryType::Credit, sender_id, amount, "Bitcoin transfer"),
+        entry(EntryType::Debit, receiver_id, received, "Bitcoin transfer"),
+    ];
+    if fee > 0 {
+        ledger_entries.push(entry(
+            EntryType::Debit,
+            &config.fee_account_id,
+            fee,
+            "Bitcoin network fee",
+        ));
+    }
+    if amount > received + fee {
+        ledger_entries.push(entry(
+            EntryType::Debit,
+            &config.outgoing_account_id,
+            amount - received - fee,
+            "Bitcoin sent",
+        ));
+    }
+    Some(ProposedTransaction {
+        transaction,
+        ledger_entries,
+    })
+}
+
+fn propose_transaction(
+    account_id: &AccountId,
+    wallet_tx: &WalletTransaction,
+    config: &ImportConfig,
+) -> Option<ProposedTransaction> {
+    let datetime = wallet_tx.confirmation_time?;
+    let net = wallet_tx.net();
+    if net == 0 {
+        return None;
+    }
+    let direction = if net > 0 {
+        TransferDirection::Incoming
+    } else {
+        TransferDirection::Outgoing
+    };
+    let rule = config
+        .rules
+        .iter()
+        .find(|rule| rule.matches(&direction, wallet_tx));
+    let counter_account_id = match (rule, &direction) {
+        (Some(rule), _) => rule.counter_account_id,
+        (None, TransferDirection::Incoming) => config.incoming_account_id,
+        (None, TransferDirection::Outgoing) => config.outgoing_account_id,
+    };
+    let description = rule
+        .and_then(|rule| rule.description.clone())
+        .unwrap_or_else(|| match direction {
+            TransferDirection::Incoming => format!("Bitcoin received {}", wallet_tx.txid),
+            TransferDirection::Outgoing => format!("Bitcoin sent {}", wallet_tx.txid),
+        });
+    let transaction = Transaction::new(
+        datetime,
+        description,
+        TransactionType::OnChain {
+            txid: wallet_tx.txid.clone(),
+        },
+    );
+
+    let btc_id = btc_currency_id();
+    let entry = |entry_type: EntryType, account_id: &AccountId, sats: u64, description: &str| {
+        LedgerEntry::new(
+            &transaction.id,
+            entry_type,
+            account_id,
+            CurrencyAmount::new(&btc_id, sats_to_btc(sats as i64)),
+            Some(description.to_string()),
+        )
+    };
+    let amount = net.unsigned_abs();
+    let ledger_entries = match direction {
+        TransferDirection::Incoming => vec![
+            entry(EntryType::Debit, account_id, amount, "Bitcoin received"),
+            entry(
+                EntryType::Credit,
+                &counter_account_id,
+                amount,
+                "Bitcoin received",
+            ),
+        ],
+        TransferDirection::Outgoing => {
+            // fees are only paid by the wallet for transactions it sends
+            let fee = wallet_tx.fee.unwrap_or_default().min(amount);
+            let mut ledger_entries =
+                vec![entry(EntryType::Credit, account_id, amount, "Bitcoin sent")];
+            if amount > fee {
+                ledger_entries.push(entry(
+                    EntryType::Debit,
+                    &counter_account_id,
+                    amount - fee,
+                    "Bitcoin sent",
+                ));
+            }
+            if fee > 0 {
+                ledger_entries.push(entry(
+                    EntryType::Debit,
+                    &config.fee_account_id,
+                    fee,
+                    "Bitcoin network fee",
+                ));
+            }
+            ledger_entries
+        }
+    };
+    Some(ProposedTransaction {
+        transaction,
+        ledger_entries,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::journal::Action::AddAccount;
+    use crate::journal::{
+        btc_currency_id, test_entries, Account, AccountType, EntryType, JournalEntry,
+        TransactionType,
+    };
+    use crate::ledger::report::AccountTotals;
+    use crate::ledger::test::setup;
+    use crate::ledger::OrganizationLedgers;
+    use crate::wallet::import::{propose_transactions, ImportConfig, ImportRule};
+    use crate::wallet::test::{bitcoin_account, test_snapshot};
+    use crate::wallet::{WalletSnapshot, WalletTransaction};
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_propose_transactions() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let account = bitcoin_account(test_entries.accounts.get(0).expect("assets"));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: account.clone(),
+                },
+            ))
+            .expect("add bitcoin account");
+
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let office_supplies_id = test_entries.accounts.get(7).expect("office supplies").id;
+        let consulting_income_id = test_entries.accounts.get(8).expect("consulting").id;
+        let mut config = ImportConfig::new(
+            &consulting_income_id,
+            &office_supplies_id,
+            &office_supplies_id,
+        );
+        let mut snapshot = test_snapshot();
+        let funding_txid = snapshot
+            .transactions
+            .get(0)
+            .expect("funding tx")
+            .txid
+            .clone();
+        config.rules.push(ImportRule {
+            txid: Some(funding_txid.clone()),
+            description: Some("Owner bitcoin funding".to_string()),
+            ..ImportRule::new(&owner_account_id)
+        });
+        snapshot.transactions.push(WalletTransaction {
+            txid: "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d".to_string(),
+            received: 9_859,
+            sent: 50_000,
+            fee: Some(141),
+            confirmation_height: Some(725_010),
+            confirmation_time: Some(datetime!(2022-02-28 12:00 UTC)),
+        });
+        snapshot.transactions.push(WalletTransaction {
+            txid: "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_string(),
+            received: 1_000,
+            sent: 0,
+            fee: None,
+            confirmation_height: None,
+            confirmation_time: None,
+        });
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let snapshots = BTreeMap::from([(account.id, snapshot.clone())]);
+        let proposed =
+            propose_transactions(&ledger, &account.id, &snapshots, &config).expect("proposed");
+        assert_eq!(proposed.len(), 2);
+
+        let funding = proposed.get(0).expect("funding");
+        assert_eq!(funding.transaction.description, "Owner bitcoin funding");
+        let credit = funding
+            .ledger_entries
+            .iter()
+            .find(|entry| entry.entry_type == EntryType::Credit)
+            .expect("credit");
+        assert_eq!(credit.account_id, owner_account_id);
+        assert_eq!(credit.currency_amount.amount, Decimal::new(50_000, 8));
+
+        let spend = proposed.get(1).expect("spend");
+        assert_eq!(spend.ledger_entries.len(), 3);
+        let fee = spend.ledger_entries.get(2).expect("fee");
+        assert_eq!(fee.currency_amount.amount, Decimal::new(141, 8));
+        let payment = spend.ledger_entries.get(1).expect("payment");
+        assert_eq!(payment.currency_amount.amount, Decimal::new(40_000, 8));
+
+        for proposed_transaction in proposed {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    proposed_transaction.into_action(),
+                ))
+                .expect("add proposed transaction");
+        }
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let transaction = ledger
+            .get_txid_transaction(&account.id, &funding_txid)
+            .expect("imported");
+        assert_eq!(
+            transaction.transaction_type,
+            TransactionType::OnChain { txid: funding_txid }
+        );
+        let proposed =
+            propose_transactions(&ledger, &account.id, &snapshots, &config).expect("proposed");
+        assert!(proposed.is_empty());
+    }
+
+    #[test]
+    fn test_propose_transfers() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let assets = test_entries.accounts.get(0).expect("assets");
+        let account = bitcoin_account(assets);
+        let savings_account = Account {
+            number: 201,
+            description: "Bitcoin Savings".to_string(),
+            account_type: AccountType::BitcoinAccount {
+                descriptor: "wpkh(savings/0/*)".to_string(),
+                change_descriptor: None,
+            },
+            ..bitcoin_account(assets)
+        };
+        for account in [account.clone(), savings_account.clone()] {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    AddAccount { account },
+                ))
+                .expect("add bitcoin account");
+        }
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let office_supplies_id = test_entries.accounts.get(7).expect("office supplies").id;
+        let config = ImportConfig::new(&owner_account_id, &office_supplies_id, &office_supplies_id);
+
+        // move bitcoin from the wallet to savings, with change back to the wallet
+        let transfer_txid =
+            "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d".to_string();
+        let mut snapshot = test_snapshot();
+        snapshot.transactions.push(WalletTransaction {
+            txid: transfer_txid.clone(),
+            received: 19_859,
+            sent: 50_000,
+            fee: Some(141),
+            confirmation_height: Some(725_010),
+            confirmation_time: Some(datetime!(2022-02-28 12:00 UTC)),
+        });
+        let savings_snapshot = WalletSnapshot {
+            balance: 30_000,
+            utxos: vec![],
+            transactions: vec![WalletTransaction {
+                txid: transfer_txid.clone(),
+                received: 30_000,
+                sent: 0,
+                fee: None,
+                confirmation_height: Some(725_010),
+                confirmation_time: Some(datetime!(2022-02-28 12:00 UTC)),
+            }],
+            ..test_snapshot()
+        };
+        let snapshots = BTreeMap::from([
+            (account.id, snapshot),
+            (savings_account.id, savings_snapshot),
+        ]);
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let proposed =
+            propose_transactions(&ledger, &account.id, &snapshots, &config).expect("proposed");
+        assert_eq!(proposed.len(), 2);
+        let transfer = proposed.get(1).expect("transfer");
+        assert_eq!(transfer.ledger_entries.len(), 3);
+        let savings_entry = transfer.ledger_entries.get(1).expect("savings");
+        assert_eq!(savings_entry.account_id, savings_account.id);
+        assert_eq!(savings_entry.entry_type, EntryType::Debit);
+        assert_eq!(
+            savings_entry.currency_amount.amount,
+            Decimal::new(30_000, 8)
+        );
+        for proposed_transaction in proposed {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    proposed_transaction.into_action(),
+                ))
+                .expect("add proposed transaction");
+        }
+
+        // the receiving wallet's side is already posted
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let proposed = propose_transactions(&ledger, &savings_account.id, &snapshots, &config)
+            .expect("proposed");
+        assert!(proposed.is_empty());
+        let savings_account = ledger
+            .get_account(&savings_account.id)
+            .expect("savings account");
+        let totals = AccountTotals::new(&ledger, savings_account, datetime!(2022-03-01 00:00 UTC));
+        assert_eq!(totals.balance(&btc_currency_id()), Decimal::new(30_000, 8));
+    }
+}
@@ -0,0 +1,517 @@
+use crate::journal::{btc_currency_id, Account, AccountId, AccountType, PaymentMethod, BTC_SCALE};
+use crate::ledger::report::AccountTotals;
+use crate::ledger::Ledger;
+use crate::wallet::import::{ImportConfig, ProposedTransaction};
+use crate::wallet::reconcile::ReconciliationReport;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+#[cfg(feature = "server")]
+pub mod bdk;
+pub mod bip21;
+pub mod import;
+pub mod reconcile;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    MissingAccount(AccountId),
+    NotBitcoinAccount(AccountId),
+    MissingWallet(AccountId),
+    InvalidCounterAccount(AccountId),
+    Backend(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingAccount(a) => write!(f, "missing account: {}", a),
+            Self::NotBitcoinAccount(a) => write!(f, "not a bitcoin account: {}", a),
+            Self::MissingWallet(a) => write!(f, "missing wallet: {}", a),
+            Self::InvalidCounterAccount(a) => write!(f, "invalid counter account: {}", a),
+            Self::Backend(e) => write!(f, "wallet backend: {}", e),
+        }
+    }
+}
+
+/// Blockchain data source used to sync a bitcoin account's descriptors
+pub trait ChainBackend {
+    fn sync(
+        &self,
+        descriptor: &str,
+        change_descriptor: Option<&str>,
+    ) -> Result<WalletSnapshot, Error>;
+
+    fn address(&self, descriptor: &str, index: u32) -> Result<String, Error>;
+}
+
+/// Unspent output of a wallet, value in satoshis
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct WalletUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+    pub change: bool,
+}
+
+/// Wallet transaction, amounts in satoshis
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct WalletTransaction {
+    pub txid: String,
+    pub received: u64,
+    pub sent: u64,
+    pub fee: Option<u64>,
+    pub confirmation_height: Option<u32>,
+    pub confirmation_time: Option<OffsetDateTime>,
+}
+
+impl WalletTransaction {
+    /// Received minus sent satoshis, negative for outgoing transactions
+    pub fn net(&self) -> i64 {
+        self.received as i64 - self.sent as i64
+    }
+}
+
+/// On-chain state of a wallet after a sync, balance in satoshis
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct WalletSnapshot {
+    pub synced: OffsetDateTime,
+    pub balance: u64,
+    pub utxos: Vec<WalletUtxo>,
+    pub transactions: Vec<WalletTransaction>,
+}
+
+/// Bitcoin account with its ledger balance and on-chain wallet state
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct AccountWallet {
+    pub account: Arc<Account>,
+    pub ledger_balance: Decimal,
+    pub onchain_balance: Decimal,
+    pub snapshot: WalletSnapshot,
+}
+
+/// Synced wallets of an organization's bitcoin accounts
+pub struct BitcoinWallets<B>
+where
+    B: ChainBackend,
+{
+    backend: B,
+    snapshot_map: BTreeMap<AccountId, WalletSnapshot>,
+}
+
+impl<B> BitcoinWallets<B>
+where
+    B: ChainBackend,
+{
+    pub fn new(backend: B) -> Self {
+        let snapshot_map = BTreeMap::new();
+        BitcoinWallets {
+            backend,
+            snapshot_map,
+        }
+    }
+
+    pub fn sync(&mut self, ledger: &Ledger, account_id: &AccountId) -> Result<(), Error> {
+        let account = ledger
+            .get_account(account_id)
+            .ok_or(Error::MissingAccount(*account_id))?;
+        match &account.account_type {
+            AccountType::BitcoinAccount {
+                descriptor,
+                change_descriptor,
+            } => {
+                let snapshot = self
+                    .backend
+                    .sync(descriptor, change_descriptor.as_deref())?;
+                self.snapshot_map.insert(account.id, snapshot);
+                Ok(())
+            }
+            _ => Err(Error::NotBitcoinAccount(account.id)),
+        }
+    }
+
+    pub fn sync_all(&mut self, ledger: &Ledger) -> Result<(), Error> {
+        for account_id in bitcoin_account_ids(ledger) {
+            self.sync(ledger, &account_id)?;
+        }
+        Ok(())
+    }
+
+    // invoice payment method with the account's next unused receive address
+    pub fn invoice_payment_method(
+        &self,
+        ledger: &Ledger,
+        account_id: &AccountId,
+    ) -> Result<PaymentMethod, Error> {
+        let account = ledger
+            .get_account(account_id)
+            .ok_or(Error::MissingAccount(*account_id))?;
+        match &account.account_type {
+            AccountType::BitcoinAccount { descriptor, .. } => {
+                let index = ledger.next_address_index(account_id);
+                let address = self.backend.address(descriptor, index)?;
+                Ok(PaymentMethod::Bitcoin {
+                    address,
+                    account_id: Some(account.id),
+                    derivation_index: Some(index),
+                })
+            }
+            _ => Err(Error::NotBitcoinAccount(account.id)),
+        }
+    }
+
+    pub fn get_snapshot(&self, account_id: &AccountId) -> Option<&WalletSnapshot> {
+        self.snapshot_map.get(account_id)
+    }
+
+    pub fn account_wallet(
+        &self,
+        ledger: &Ledger,
+        account_id: &AccountId,
+        date_time: OffsetDateTime,
+    ) -> Result<AccountWallet, Error> {
+        let account = ledger
+            .get_account(account_id)
+            .ok_or(Error::MissingAccount(*account_id))?;
+        let snapshot = self
+            .get_snapshot(account_id)
+            .cloned()
+            .ok_or(Error::MissingWallet(*account_id))?;
+        let ledger_balance =
+            AccountTotals::new(ledger, account.clone(), date_time).balance(&btc_currency_id());
+        let onchain_balance = sats_to_btc(snapshot.balance as i64);
+        Ok(AccountWallet {
+            account,
+            ledger_balance,
+            onchain_balance,
+            snapshot,
+        })
+    }
+
+    pub fn reconcile(
+        &self,
+        ledger: &Ledger,
+        account_id: &AccountId,
+        date_time: OffsetDateTime,
+        block_height: Option<u32>,
+    ) -> Result<ReconciliationReport, Error> {
+        let snapshot = self
+            .get_snapshot(account_id)
+            .ok_or(Error::MissingWallet(*account_id))?;
+        ReconciliationReport::new(ledger, account_id, snapshot, date_time, block_height)
+    }
+
+    pub fn propose_transactions(
+        &self,
+        ledger: &Ledger,
+        account_id: &AccountId,
+        config: &ImportConfig,
+    ) -> Result<Vec<ProposedTransaction>, Error> {
+        import::propose_transactions(ledger, account_id, &self.snapshot_map, config)
+    }
+}
+
+pub fn bitcoin_account_ids(ledger: &Ledger) -> Vec<AccountId> {
+    ledger
+        .accounts()
+        .iter()
+        .filter(|account| matches!(account.account_type, AccountType::BitcoinAccount { .. }))
+        .map(|account| account.id)
+        .collect()
+}
+
+pub fn sats_to_btc(sats: i64) -> Decimal {
+    Decimal::new(sats, BTC_SCALE)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::journal::Action::{AddAccount, AddTransaction};
+    use crate::journal::{
+        btc_currency_id, test_entries, Account, AccountCategory, AccountType, BalanceSheetCategory,
+        CurrencyAmount, EntryType, JournalEntry, LedgerEntry, PaymentMethod, PaymentTerms,
+        Transaction, TransactionType,
+    };
+    use crate::ledger::test::setup;
+    use crate::ledger::{Error as LedgerError, OrganizationLedgers};
+    use crate::wallet::bip21::PaymentUri;
+    use crate::wallet::{
+        BitcoinWallets, ChainBackend, Error, WalletSnapshot, WalletTransaction, WalletUtxo,
+    };
+    use rust_decimal::Decimal;
+    use rusty_ulid::Ulid;
+    use std::collections::BTreeMap;
+    use time::macros::datetime;
+    use time::OffsetDateTime;
+
+    pub const TEST_DESCRIPTOR: &str = "wpkh(tpubD6NzVbkrYhZ4Xferm7Pz4VnjdcDPFyjVu5K4iZXQ4pVN8Cks4pHVowTBXBKRhX64pkRyJZJN5xAKj4UDNnLPb5p2sSKXhewoYx5GbTdUFWq/0/*)";
+
+    /// In-process backend returning preset snapshots by descriptor
+    #[derive(Default)]
+    pub struct MockBackend {
+        snapshot_map: BTreeMap<String, WalletSnapshot>,
+    }
+
+    impl MockBackend {
+        pub fn new() -> Self {
+            MockBackend::default()
+        }
+
+        pub fn insert(&mut self, descriptor: &str, snapshot: WalletSnapshot) {
+            self.snapshot_map.insert(descriptor.to_string(), snapshot);
+        }
+    }
+
+    impl ChainBackend for MockBackend {
+        fn sync(
+            &self,
+            descriptor: &str,
+            _change_descriptor: Option<&str>,
+        ) -> Result<WalletSnapshot, Error> {
+            self.snapshot_map
+                .get(descriptor)
+                .cloned()
+                .ok_or(Error::Backend(format!(
+                    "unknown descriptor: {}",
+                    descriptor
+                )))
+        }
+
+        fn address(&self, descriptor: &str, index: u32) -> Result<String, Error> {
+            if !self.snapshot_map.contains_key(descriptor) {
+                return Err(Error::Backend(format!(
+                    "unknown descriptor: {}",
+                    descriptor
+                )));
+            }
+            Ok(format!("mock-address-{}", index))
+        }
+    }
+
+    pub fn test_snapshot() -> WalletSnapshot {
+        WalletSnapshot {
+            synced: datetime!(2022-03-01 00:00 UTC),
+            balance: 50_000,
+            utxos: vec![WalletUtxo {
+                txid: "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+                    .to_string(),
+                vout: 0,
+                value: 50_000,
+                change: false,
+            }],
+            transactions: vec![WalletTransaction {
+                txid: "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+                    .to_string(),
+                received: 50_000,
+                sent: 0,
+                fee: Some(141),
+                confirmation_height: Some(725_000),
+                confirmation_time: Some(datetime!(2022-02-27 12:00 UTC)),
+            }],
+        }
+    }
+
+    pub fn bitcoin_account(assets_account: &Account) -> Account {
+        Account::new(
+            Some(&assets_account.id),
+            200,
+            "Bitcoin Wallet".to_string(),
+            AccountType::BitcoinAccount {
+                descriptor: TEST_DESCRIPTOR.to_string(),
+                change_descriptor: None,
+            },
+            AccountCategory::BalanceSheet(BalanceSheetCategory::Asset),
+        )
+    }
+
+    #[test]
+    fn test_sync_account_wallet() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let account = bitcoin_account(test_entries.accounts.get(0).expect("assets"));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: account.clone(),
+                },
+            ))
+            .expect("add bitcoin account");
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+
+        let mut backend = MockBackend::new();
+        backend.insert(TEST_DESCRIPTOR, test_snapshot());
+        let mut wallets = BitcoinWallets::new(backend);
+        wallets.sync_all(&ledger).expect("sync");
+
+        let account_wallet = wallets
+            .account_wallet(&ledger, &account.id, OffsetDateTime::now_utc())
+            .expect("account wallet");
+        assert_eq!(account_wallet.ledger_balance, Decimal::ZERO);
+        assert_eq!(account_wallet.onchain_balance, Decimal::new(50_000, 8));
+        assert_eq!(account_wallet.snapshot.utxos.len(), 1);
+        assert_eq!(account_wallet.snapshot.transactions.len(), 1);
+
+        let bank_account_id = test_entries.accounts.get(6).expect("bank").id;
+        let result = wallets.sync(&ledger, &bank_account_id);
+        assert!(matches!(result, Err(Error::NotBitcoinAccount(_))));
+    }
+
+    #[test]
+    fn test_invoice_payment_method() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let account = bitcoin_account(test_entries.accounts.get(0).expect("assets"));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: account.clone(),
+                },
+            ))
+            .expect("add bitcoin account");
+        let mut backend = MockBackend::new();
+        backend.insert(TEST_DESCRIPTOR, test_snapshot());
+        let wallets = BitcoinWallets::new(backend);
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let payment_method = wallets
+            .invoice_payment_method(&ledger, &account.id)
+            .expect("payment method");
+        assert_eq!(
+            payment_method,
+            PaymentMethod::Bitcoin {
+                address: "mock-address-0".to_string(),
+                account_id: Some(account.id),
+                derivation_index: Some(0),
+            }
+        );
+
+        let transaction = Transaction::new(
+            datetime!(2022-03-01 09:00 UTC),
+            "Consulting invoice".to_string(),
+            TransactionType::Invoice {
+                payment_method,
+                payment_terms: PaymentTerms::ImmediatePayment,
+                payments: vec![],
+                line_items: vec![],
+            },
+        );
+        let receivable_id = account.id;
+        let income_id = test_entries.accounts.get(8).expect("consulting").id;
+        let amount = CurrencyAmount::new(&btc_currency_id(), Decimal::new(50_000, 8));
+        let ledger_entries = vec![
+            LedgerEntry::new(
+                &transaction.id,
+                EntryType::Debit,
+                &receivable_id,
+                amount.clone(),
+                None,
+            ),
+            LedgerEntry::new(&transaction.id, EntryType::Credit, &income_id, amount, None),
+        ];
+        let add_invoice = AddTransaction {
+            transaction: transaction.clone(),
+            ledger_entries: ledger_entries.clone(),
+        };
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(organization_id, add_invoice))
+            .expect("add invoice");
+
+        let uri = PaymentUri::for_invoice(&transaction, Some(Decimal::new(50_000, 8)), None)
+            .expect("payment uri");
+        assert_eq!(
+            uri.to_string(),
+            "bitcoin:mock-address-0?amount=0.0005&message=Consulting%20invoice"
+        );
+
+        // reused derivation index is rejected
+        let mut reused = transaction.clone();
+        reused.id = Ulid::generate();
+        let ledger_entries = ledger_entries
+            .into_iter()
+            .map(|entry| LedgerEntry {
+                transaction_id: reused.id,
+                ..entry
+            })
+            .collect();
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            AddTransaction {
+                transaction: reused,
+                ledger_entries,
+            },
+        ));
+        assert!(matches!(result, Err(LedgerError::AddressIndexUsed(_, 0))));
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        assert_eq!(ledger.next_address_index(&account.id), 1);
+        let payment_method = wallets
+            .invoice_payment_method(&ledger, &account.id)
+            .expect("payment method");
+        assert!(matches!(
+            payment_method,
+            PaymentMethod::Bitcoin {
+                derivation_index: Some(1),
+                ..
+            }
+        ));
+
+        // skipping past the next derivation index is rejected
+        let skipped = Transaction::new(
+            datetime!(2022-03-02 09:00 UTC),
+            "Consulting invoice".to_string(),
+            TransactionType::Invoice {
+                payment_method: PaymentMethod::Bitcoin {
+                    address: "mock-address-2".to_string(),
+                    account_id: Some(account.id),
+                    derivation_index: Some(2),
+                },
+                payment_terms: PaymentTerms::ImmediatePayment,
+                payments: vec![],
+                line_items: vec![],
+            },
+        );
+        let amount = CurrencyAmount::new(&btc_currency_id(), Decimal::new(50_000, 8));
+        let ledger_entries = vec![
+            LedgerEntry::new(
+                &skipped.id,
+                EntryType::Debit,
+                &receivable_id,
+                amount.clone(),
+                None,
+            ),
+            LedgerEntry::new(&skipped.id, EntryType::Credit, &income_id, amount, None),
+        ];
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            AddTransaction {
+                transaction: skipped,
+                ledger_entries,
+            },
+        ));
+        assert!(matches!(
+            result,
+            Err(LedgerError::InvalidAddressIndex(_, 2))
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::journal::{
+    btc_currency_id, Account, AccountId, AccountType, CurrencyAmount, Reconciliation, Transaction,
+    TransactionType,
+};
+use crate::ledger::report::AccountTotals;
+use crate::ledger::Ledger;
+use crate::wallet::{sats_to_btc, Error, WalletSnapshot, WalletTransaction};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// Comparison of a bitcoin account's ledger transactions and balance with its confirmed wallet
+/// transactions at a date time and optional block height
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ReconciliationReport {
+    pub account: Arc<Account>,
+    pub date_time: OffsetDateTime,
+    pub block_height: Option<u32>,
+    pub ledger_balance: Decimal,
+    pub onchain_balance: Decimal,
+    pub unmatched_wallet_transactions: Vec<WalletTransaction>,
+    pub unmatched_transactions: Vec<Arc<Transaction>>,
+    pub unreconciled_transactions: Vec<Arc<Transaction>>,
+}
+
+impl ReconciliationReport {
+    pub fn new(
+        ledger: &Ledger,
+        account_id: &AccountId,
+        snapshot: &WalletSnapshot,
+        date_time: OffsetDateTime,
+        block_height: Option<u32>,
+    ) -> Result<Self, Error> {
+        let account = ledger
+            .get_account(account_id)
+            .ok_or(Error::MissingAccount(*account_id))?;
+        if !matches!(account.account_type, AccountType::BitcoinAccount { .. }) {
+            return Err(Error::NotBitcoinAccount(account.id));
+        }
+
+        // wallet transactions confirmed at or before the date time and block height
+        let wallet_transactions: Vec<&WalletTransaction> = snapshot
+            .transactions
+            .iter()
+            .filter(|wallet_tx| {
+                wallet_tx
+                    .confirmation_time
+                    .is_some_and(|confirmed| confirmed <= date_time)
+                    && block_height.is_none_or(|height| {
+                        wallet_tx
+                            .confirmation_height
+                            .is_some_and(|confirmed| confirmed <= height)
+                    })
+            })
+            .collect();
+        let onchain_sats: i64 = wallet_transactions
+            .iter()
+            .map(|wallet_tx| wallet_tx.net())
+            .sum();
+        let onchain_balance = sats_to_btc(onchain_sats);
+        let ledger_balance =
+            AccountTotals::new(ledger, account.clone(), date_time).balance(&btc_currency_id());
+
+        let transaction_ids: BTreeSet<_> = ledger
+            .get_account_entries_period(&account.id, None, &date_time)
+            .iter()
+            .map(|entry| entry.transaction_id)
+            .collect();
+        let transactions: Vec<Arc<Transaction>> = transaction_ids
+            .iter()
+            .filter_map(|transaction_id| ledger.get_transaction(transaction_id))
+            .collect();
+        let transaction_txid = |transaction: &Transaction| match &transaction.transaction_type {
+            TransactionType::OnChain { txid } => Some(txid.clone()),
+            _ => None,
+        };
+
+        let ledger_txids: BTreeSet<String> = transactions
+            .iter()
+            .filter_map(|transaction| transaction_txid(transaction))
+            .collect();
+        let unmatched_wallet_transactions = wallet_transactions
+            .iter()
+            .filter(|wallet_tx| !ledger_txids.contains(&wallet_tx.txid))
+            .map(|wallet_tx| (*wallet_tx).clone())
+            .collect();
+
+        // previously reconciled transactions match without an on-chain txid
+        let wallet_txids: BTreeSet<&String> = wallet_transactions
+            .iter()
+            .map(|wallet_tx| &wallet_tx.txid)
+            .collect();
+        let (matched_transactions, unmatched_transactions): (Vec<_>, Vec<_>) =
+            transactions.into_iter().partition(|transaction| {
+                ledger
+                    .get_reconciliation(&account.id, &transaction.id)
+                    .is_some()
+                    || transaction_txid(transaction)
+                        .is_some_and(|txid| wallet_txids.contains(&txid))
+            });
+        let unreconciled_transactions = matched_transactions
+            .into_iter()
+            .filter(|transaction| {
+                ledger
+                    .get_reconciliation(&account.id, &transaction.id)
+                    .is_none()
+            })
+            .collect();
+
+        Ok(ReconciliationReport {
+            account,
+            date_time,
+            block_height,
+            ledger_balance,
+            onchain_balance,
+            unmatched_wallet_transactions,
+            unmatched_transactions,
+            unreconciled_transactions,
+        })
+    }
+
+    // ledger balance minus on-chain balance
+    pub fn difference(&self) -> Decimal {
+        self.ledger_balance - self.onchain_balance
+    }
+
+    pub fn is_reconciled(&self) -> bool {
+        self.difference().is_zero()
+            && self.unmatched_wallet_transactions.is_empty()
+            && self.unmatched_transactions.is_empty()
+    }
+
+    // reconciliation of matched transactions not yet marked as reconciled, recording the on-chain
+    // balance and the ledger's difference from it
+    pub fn reconciliation(&self, datetime: OffsetDateTime) -> Option<Reconciliation> {
+        if self.unreconciled_transactions.is_empty() {
+            return None;
+        }
+        let transaction_ids = self
+            .unreconciled_transactions
+            .iter()
+            .map(|transaction| transaction.id)
+            .collect();
+        Some(Reconciliation::new(
+            &self.account.id,
+            datetime,
+            transaction_ids,
+            Some(CurrencyAmount::new(
+                &btc_currency_id(),
+                self.onchain_balance,
+            )),
+            self.difference(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::journal::Action::{AddAccount, AddTransaction, Reconcile};
+    use crate::journal::{
+        btc_currency_id, test_entries, CurrencyAmount, EntryType, JournalEntry, LedgerEntry,
+        Reconciliation, Transaction, TransactionType,
+    };
+    use crate::ledger::test::setup;
+    use crate::ledger::{Error, OrganizationLedgers};
+    use crate::wallet::import::{propose_transactions, ImportConfig};
+    use crate::wallet::reconcile::ReconciliationReport;
+    use crate::wallet::test::{bitcoin_account, test_snapshot};
+    use crate::wallet::WalletTransaction;
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_reconciliation_report() {
+        setup();
+        let test_entries = test_entries();
+        let organization_id = test_entries.organization.id;
+        let organization_ledgers = &mut OrganizationLedgers::new();
+        organization_ledgers
+            .add_journal_entries(test_entries.journal_entries)
+            .expect("load journal");
+        let account = bitcoin_account(test_entries.accounts.get(0).expect("assets"));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddAccount {
+                    account: account.clone(),
+                },
+            ))
+            .expect("add bitcoin account");
+        let owner_account_id = test_entries.accounts.get(5).expect("owner").id;
+        let office_supplies_id = test_entries.accounts.get(7).expect("office supplies").id;
+
+        // import the funding transaction and record a transfer missing on-chain
+        let mut snapshot = test_snapshot();
+        let config = ImportConfig::new(&owner_account_id, &office_supplies_id, &office_supplies_id);
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let snapshots = BTreeMap::from([(account.id, snapshot.clone())]);
+        let proposed =
+            propose_transactions(&ledger, &account.id, &snapshots, &config).expect("proposed");
+        for proposed_transaction in proposed {
+            organization_ledgers
+                .add_journal_entry(JournalEntry::new_gen_id(
+                    organization_id,
+                    proposed_transaction.into_action(),
+                ))
+                .expect("add proposed transaction");
+        }
+        let btc_id = btc_currency_id();
+        let transaction = Transaction::new(
+            datetime!(2022-02-28 09:00 UTC),
+            "Bitcoin purchase".to_string(),
+            TransactionType::LedgerAdjustment,
+        );
+        let amount = CurrencyAmount::new(&btc_id, Decimal::new(10_000, 8));
+        let ledger_entries = vec![
+            LedgerEntry::new(
+                &transaction.id,
+                EntryType::Debit,
+                &account.id,
+                amount.clone(),
+                None,
+            ),
+            LedgerEntry::new(
+                &transaction.id,
+                EntryType::Credit,
+                &owner_account_id,
+                amount,
+                None,
+            ),
+        ];
+        let manual_transaction_id = transaction.id;
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                AddTransaction {
+                    transaction,
+                    ledger_entries,
+                },
+            ))
+            .expect("add manual transaction");
+        snapshot.transactions.push(WalletTransaction {
+            txid: "a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d".to_string(),
+            received: 20_000,
+            sent: 0,
+            fee: None,
+            confirmation_height: Some(725_010),
+            confirmation_time: Some(datetime!(2022-02-28 12:00 UTC)),
+        });
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let date_time = datetime!(2022-03-01 00:00 UTC);
+        let report = ReconciliationReport::new(&ledger, &account.id, &snapshot, date_time, None)
+            .expect("report");
+        assert_eq!(report.ledger_balance, Decimal::new(60_000, 8));
+        assert_eq!(report.onchain_balance, Decimal::new(70_000, 8));
+        assert_eq!(report.difference(), Decimal::new(-10_000, 8));
+        assert_eq!(report.unmatched_wallet_transactions.len(), 1);
+        assert_eq!(report.unmatched_transactions.len(), 1);
+        assert_eq!(report.unreconciled_transactions.len(), 1);
+        assert!(!report.is_reconciled());
+
+        // block height before the second wallet transaction
+        let report =
+            ReconciliationReport::new(&ledger, &account.id, &snapshot, date_time, Some(725_000))
+                .expect("report");
+        assert_eq!(report.onchain_balance, Decimal::new(50_000, 8));
+        assert!(report.unmatched_wallet_transactions.is_empty());
+
+        // reconciled manual transaction is no longer unmatched, the difference from the on-chain
+        // balance is recorded and must match the ledger balance
+        let mut reconciliation = report.reconciliation(date_time).expect("reconciliation");
+        reconciliation.transaction_ids.push(manual_transaction_id);
+        assert_eq!(reconciliation.difference, Decimal::new(10_000, 8));
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            Reconcile {
+                reconciliation: Reconciliation {
+                    difference: Decimal::ZERO,
+                    ..reconciliation.clone()
+                },
+            },
+        ));
+        assert!(matches!(result, Err(Error::UnbalancedReconciliation(_))));
+        organization_ledgers
+            .add_journal_entry(JournalEntry::new_gen_id(
+                organization_id,
+                Reconcile {
+                    reconciliation: reconciliation.clone(),
+                },
+            ))
+            .expect("reconcile");
+        let result = organization_ledgers.add_journal_entry(JournalEntry::new_gen_id(
+            organization_id,
+            Reconcile { reconciliation },
+        ));
+        assert!(matches!(result, Err(Error::TransactionReconciled(_))));
+
+        let ledger = organization_ledgers
+            .get_ledger(&organization_id)
+            .expect("ledger");
+        let report = ReconciliationReport::new(&ledger, &account.id, &snapshot, date_time, None)
+            .expect("report");
+        assert!(report.unmatched_transactions.is_empty());
+        assert!(report.unreconciled_transactions.is_empty());
+        assert!(report.reconciliation(date_time).is_none());
+    }
+}